@@ -0,0 +1,262 @@
+use std::{
+    fs::{ File, Metadata },
+    io::{ self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write },
+};
+
+use crate::{ Args, color, error::Error, output::Output, print::SNIP_MARKER };
+
+const ROW_LEN: usize = 16;
+const OFFSET_COLOR: &str = "\x1b[90m";
+const NUL_COLOR: &str = "\x1b[90m";
+const PRINTABLE_COLOR: &str = "\x1b[36m";
+const WHITESPACE_COLOR: &str = "\x1b[32m";
+const CONTROL_COLOR: &str = "\x1b[35m";
+const NON_ASCII_COLOR: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+const NUL_CHAR: char = '⋄';
+const WHITESPACE_CHAR: char = '_';
+const CONTROL_CHAR: char = '•';
+const NON_ASCII_CHAR: char = '×';
+const PANE_BORDER: char = '│';
+
+// file signatures, some of which are only a couple of bytes long so they're
+// only trusted when the content doesn't look like text
+const MAGIC_NUMBERS: [(&[u8], &str); 26] = [
+    (b"\x7fELF", "ELF executable"),
+    (b"MZ", "DOS/Windows executable"),
+    (b"\xfe\xed\xfa\xce", "Mach-O executable"),
+    (b"\xfe\xed\xfa\xcf", "Mach-O executable"),
+    (b"\xce\xfa\xed\xfe", "Mach-O executable"),
+    (b"\xcf\xfa\xed\xfe", "Mach-O executable"),
+    (b"\xca\xfe\xba\xbe", "Java class or Mach-O universal binary"),
+    (b"\x00asm", "WebAssembly binary"),
+    (b"!<arch>\n", "ar archive"),
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"BM", "BMP image"),
+    (b"RIFF", "RIFF data"),
+    (b"OggS", "Ogg data"),
+    (b"fLaC", "FLAC audio"),
+    (b"ID3", "MP3 audio"),
+    (b"%PDF-", "PDF document"),
+    (b"SQLite format 3\x00", "SQLite database"),
+    (b"PK\x03\x04", "Zip archive"),
+    (b"\x1f\x8b", "gzip compressed data"),
+    (b"BZh", "bzip2 compressed data"),
+    (b"\xfd7zXZ\x00", "XZ compressed data"),
+    (b"\x28\xb5\x2f\xfd", "Zstandard compressed data"),
+    (b"7z\xbc\xaf\x27\x1c", "7-zip archive"),
+];
+const UNKNOWN_TYPE: &str = "data";
+
+/// Looks at the start of a file and returns a description of its type if it
+/// isn't text.
+pub fn get_binary_type(start: &[u8]) -> Option<&'static str> {
+    let magic_type = MAGIC_NUMBERS.iter()
+        .find(|(magic, _)| start.starts_with(magic))
+        .map(|(_, file_type)| *file_type);
+
+    // a character can be cut off at the end of the sniffed bytes
+    let is_utf8 = match std::str::from_utf8(start) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    };
+
+    if start.contains(&0) {
+        Some(magic_type.unwrap_or(UNKNOWN_TYPE))
+    } else if !is_utf8 {
+        magic_type
+    } else {
+        None
+    }
+}
+
+/// Prints the file as rows of offsets, hex bytes and their ASCII characters.
+/// Line ranges are taken as byte ranges.
+pub fn print_hexdump(path: &str, mut reader: BufReader<File>, md: &Metadata, out: &mut Output,
+    args: &Args) -> Result<(), Error> {
+    let read_err = |e| Error::path(path, e);
+
+    let counts_from_end = args.line_range.iter().any(|r| r.counts_from_end());
+
+    // like with lines, ranges counted from the end need to know the file size,
+    // which only regular files can tell without being read
+    let size = if md.is_file() {
+        md.len() as usize
+    } else if counts_from_end {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents).map_err(read_err)?;
+        let size = contents.len();
+        return print_rows(path, Cursor::new(contents), 0, size, out, args);
+    } else {
+        usize::MAX
+    };
+
+    // skip straight to the first row with something to show
+    let first_byte = args.line_range.iter()
+        .map(|r| r.resolve(size).0)
+        .min()
+        .unwrap_or(1);
+    let skip = (first_byte - 1) / ROW_LEN * ROW_LEN;
+    if md.is_file() {
+        reader.seek(SeekFrom::Start(skip as u64)).map_err(read_err)?;
+    } else {
+        io::copy(&mut reader.by_ref().take(skip as u64), &mut io::sink()).map_err(read_err)?;
+    }
+
+    print_rows(path, reader, skip, size, out, args)
+}
+
+fn print_rows(path: &str, mut reader: impl BufRead, mut offset: usize, size: usize,
+    out: &mut Output, args: &Args) -> Result<(), Error> {
+    let colored = color::enabled(args);
+
+    let ranges: Vec<(usize, usize)> = args.line_range.iter()
+        .map(|r| r.resolve(size))
+        .collect();
+    let last_byte = ranges.iter().map(|r| r.1).max().unwrap_or(usize::MAX);
+
+    let mut last_printed: Option<usize> = None;
+    let mut row = [0; ROW_LEN];
+
+    while offset < last_byte {
+        let len = read_row(&mut reader, &mut row).map_err(|e| Error::path(path, e))?;
+        if len == 0 {
+            break;
+        }
+
+        // bytes are numbered from 1 like lines
+        let shown: Vec<bool> = (offset + 1..=offset + len)
+            .map(|n| ranges.is_empty() || ranges.iter().any(|&(s, e)| s <= n && n <= e))
+            .collect();
+
+        if shown.contains(&true) {
+            // mark the rows skipped between ranges
+            if last_printed.is_some_and(|l| l + ROW_LEN < offset) {
+                writeln!(out, "{}", SNIP_MARKER)?;
+            }
+            last_printed = Some(offset);
+
+            write_row(offset, &row[..len], &shown, colored, out)?;
+        }
+
+        offset += len;
+    }
+
+    Ok(())
+}
+
+// fills the row from the reader, which can take several reads from a pipe
+fn read_row(reader: &mut impl Read, row: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < row.len() {
+        match reader.read(&mut row[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
+
+fn write_row(offset: usize, bytes: &[u8], shown: &[bool], colored: bool, out: &mut Output)
+    -> io::Result<()> {
+    let mut line = String::new();
+
+    line.push_str(&paint(OFFSET_COLOR, &format!("{:08x}", offset), colored));
+    line.push_str("  ");
+
+    // bytes outside the ranges leave a gap in the row
+    let cells: Vec<Option<u8>> = (0..ROW_LEN)
+        .map(|i| bytes.get(i).copied().filter(|_| shown[i]))
+        .collect();
+
+    for (i, cell) in cells.iter().enumerate() {
+        if i == ROW_LEN / 2 {
+            line.push(' ');
+        }
+
+        match cell {
+            Some(b) => {
+                let (color, _) = get_byte_style(*b);
+                line.push_str(&paint(color, &format!("{:02x}", b), colored));
+                line.push(' ');
+            },
+            _ => line.push_str("   "),
+        }
+    }
+
+    line.push(PANE_BORDER);
+    for cell in cells {
+        match cell {
+            Some(b) => {
+                let (color, c) = get_byte_style(b);
+                line.push_str(&paint(color, &c.to_string(), colored));
+            },
+            _ => line.push(' '),
+        }
+    }
+    line.push(PANE_BORDER);
+
+    writeln!(out, "{}", line)
+}
+
+// color and ASCII pane character for a byte
+fn get_byte_style(b: u8) -> (&'static str, char) {
+    match b {
+        0 => (NUL_COLOR, NUL_CHAR),
+        b' ' => (WHITESPACE_COLOR, ' '),
+        b'\t' | b'\n' | b'\r' | 0x0b | 0x0c => (WHITESPACE_COLOR, WHITESPACE_CHAR),
+        b if b.is_ascii_graphic() => (PRINTABLE_COLOR, b as char),
+        b if b.is_ascii() => (CONTROL_COLOR, CONTROL_CHAR),
+        _ => (NON_ASCII_COLOR, NON_ASCII_CHAR),
+    }
+}
+
+fn paint(color: &str, text: &str, colored: bool) -> String {
+    if colored {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_is_not_binary() {
+        assert_eq!(get_binary_type(b"fn main() {}\n"), None);
+        assert_eq!(get_binary_type("héllo wörld".as_bytes()), None);
+        assert_eq!(get_binary_type(b""), None);
+    }
+
+    #[test]
+    fn cut_off_character_is_still_text() {
+        let text = "naïve".as_bytes();
+        assert_eq!(get_binary_type(&text[..3]), None);
+    }
+
+    #[test]
+    fn nul_bytes_are_binary() {
+        assert_eq!(get_binary_type(b"abc\x00def"), Some(UNKNOWN_TYPE));
+        assert_eq!(get_binary_type(b"\x7fELF\x02\x01\x01\x00"), Some("ELF executable"));
+    }
+
+    #[test]
+    fn invalid_utf8_with_magic_is_binary() {
+        assert_eq!(get_binary_type(b"\x89PNG\r\n\x1a\n\xff"), Some("PNG image"));
+        assert_eq!(get_binary_type(b"\x1f\x8b\x08\xff"), Some("gzip compressed data"));
+    }
+
+    #[test]
+    fn short_magic_needs_binary_content() {
+        // latin-1 text isn't utf-8 but has no signature either
+        assert_eq!(get_binary_type(b"caf\xe9 au lait"), None);
+        assert_eq!(get_binary_type(b"MZ is a text file"), None);
+        assert_eq!(get_binary_type(b"BM\n"), None);
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::{ metadata, symlink_metadata },
+    io::{ self, IsTerminal },
+    os::unix::fs::{ FileTypeExt, PermissionsExt },
+};
+use unicode_width::UnicodeWidthStr;
+
+use crate::Args;
+
+const LS_COLORS_VAR: &str = "LS_COLORS";
+// column colors. some of their keys mean something else in LS_COLORS, like su
+// and lc, so the two are kept apart
+const EB_COLORS_VARS: [&str; 2] = ["EXA_COLORS", "EB_COLORS"];
+// the keys taken from LS_COLORS besides *.ext patterns
+const FILE_KEYS: [&str; 11] = ["di", "ln", "so", "pi", "bd", "cd", "or", "mi", "ex", "fi", "no"];
+const NO_COLOR_VAR: &str = "NO_COLOR";
+// the value of ln that colors links like the file they point to
+const LINK_TARGET: &str = "target";
+const EXECUTABLE_BITS: u32 = 0o111;
+
+// the defaults of GNU ls for file types
+const DEFAULT_FILE_COLORS: [(&str, &str); 9] = [
+    ("di", "01;34"), ("ln", "01;36"), ("so", "01;35"), ("pi", "40;33"), ("bd", "40;33;01"),
+    ("cd", "40;33;01"), ("or", "40;31;01"), ("mi", "40;31;01"), ("ex", "01;32"),
+];
+
+// exa-like colors for the columns, which are the only keys read from EB_COLORS
+const DEFAULT_COLUMN_COLORS: [(&str, &str); 35] = [
+    // permissions of the user, group and others, and missing ones
+    ("ur", "1;33"), ("uw", "1;31"), ("ux", "1;32"),
+    ("gr", "33"), ("gw", "31"), ("gx", "32"),
+    ("tr", "33"), ("tw", "31"), ("tx", "32"), ("xx", "90"),
+    // setuid, setgid and sticky bits, permissions in octal and attribute markers
+    ("su", "35"), ("oc", "35"), ("xa", ""),
+    // sizes by scale, from bytes to terabytes and above
+    ("nb", "32"), ("nk", "1;32"), ("nm", "1;33"), ("ng", "1;31"), ("nt", "1;35"),
+    // the current user and group, and everyone else
+    ("uu", "1;33"), ("un", ""), ("gu", "1;33"), ("gn", ""),
+    // timestamps, inodes, links, blocks and the header row
+    ("da", "34"), ("in", "35"), ("lc", "1;31"), ("bl", "36"), ("hd", "4"),
+    // the branches of trees
+    ("tb", "90"),
+    // git statuses: new, modified, deleted, renamed, type changed, ignored and conflicted
+    ("ga", "32"), ("gm", "34"), ("gd", "31"), ("gv", "33"), ("gt", "35"), ("gi", "90"),
+    ("gc", "91"),
+];
+
+/// Whether output should be colored, from `--color` and, for auto, whether
+/// stdout is a terminal and `NO_COLOR` isn't set.
+pub fn enabled(args: &Args) -> bool {
+    match args.color.as_str() {
+        "always" => true,
+        "never" => false,
+        _ => io::stdout().is_terminal()
+            && env::var_os(NO_COLOR_VAR).is_none_or(|v| v.is_empty()),
+    }
+}
+
+/// The colors used for listing, as SGR codes. File names are colored by the
+/// two letter file type codes of `LS_COLORS` and glob patterns like `*.rs`,
+/// columns by the codes of `EXA_COLORS` and `EB_COLORS`.
+pub struct Colors {
+    enabled: bool,
+    // whether links are colored like what they point to
+    dereference: bool,
+    file_codes: HashMap<String, String>,
+    column_codes: HashMap<String, String>,
+}
+
+impl Colors {
+    pub fn load(args: &Args) -> Colors {
+        let enabled = enabled(args);
+        let mut file_codes = HashMap::new();
+        let mut column_codes = HashMap::new();
+
+        if enabled {
+            for (key, code) in DEFAULT_FILE_COLORS {
+                file_codes.insert(key.to_string(), code.to_string());
+            }
+            for (key, code) in DEFAULT_COLUMN_COLORS {
+                column_codes.insert(key.to_string(), code.to_string());
+            }
+
+            let is_file_key = |key: &str| key.starts_with('*') || FILE_KEYS.contains(&key);
+            read_codes(&[LS_COLORS_VAR], is_file_key, &mut file_codes);
+
+            let is_column_key = |key: &str| DEFAULT_COLUMN_COLORS.iter().any(|(k, _)| *k == key);
+            read_codes(&EB_COLORS_VARS, is_column_key, &mut column_codes);
+        }
+
+        Colors { enabled, dereference: args.dereference, file_codes, column_codes }
+    }
+
+    /// Colors that leave text as it is, for output read by other programs.
+    pub fn plain() -> Colors {
+        Colors {
+            enabled: false,
+            dereference: false,
+            file_codes: HashMap::new(),
+            column_codes: HashMap::new(),
+        }
+    }
+
+    /// Wraps text in the color set for a column key, if there is one.
+    pub fn paint(&self, key: &str, text: &str) -> String {
+        match self.column_codes.get(key) {
+            Some(code) if self.enabled && !code.is_empty() => paint_code(code, text),
+            _ => text.to_string(),
+        }
+    }
+
+    /// Wraps text in the color set for a file type key like `di`, for type
+    /// characters and link targets that don't exist.
+    pub fn paint_type(&self, key: &str, text: &str) -> String {
+        match self.file_codes.get(key) {
+            Some(code) if self.enabled && !code.is_empty() && code != LINK_TARGET => {
+                paint_code(code, text)
+            },
+            _ => text.to_string(),
+        }
+    }
+
+    /// Colors a file name by the type of the file at path, or by its extension
+    /// for regular files.
+    pub fn paint_name(&self, path: &str, name: &str) -> String {
+        if !self.enabled {
+            return name.to_string();
+        }
+
+        match self.get_name_code(path, name) {
+            Some(code) if !code.is_empty() => paint_code(code, name),
+            _ => name.to_string(),
+        }
+    }
+
+    fn get_name_code(&self, path: &str, name: &str) -> Option<&String> {
+        // with --dereference links are only told apart when they're broken
+        let md = if self.dereference {
+            metadata(path).or_else(|_| symlink_metadata(path)).ok()?
+        } else {
+            symlink_metadata(path).ok()?
+        };
+        let file_type = md.file_type();
+
+        let key = if file_type.is_symlink() {
+            match metadata(path) {
+                Err(_) => "or",
+                // links can be colored like what they point to
+                Ok(target) if self.file_codes.get("ln").is_some_and(|c| c == LINK_TARGET) => {
+                    if target.is_dir() {
+                        "di"
+                    } else {
+                        return self.get_file_code(name, target.permissions().mode());
+                    }
+                },
+                Ok(_) => "ln",
+            }
+        } else if file_type.is_dir() {
+            "di"
+        } else if file_type.is_fifo() {
+            "pi"
+        } else if file_type.is_socket() {
+            "so"
+        } else if file_type.is_block_device() {
+            "bd"
+        } else if file_type.is_char_device() {
+            "cd"
+        } else {
+            return self.get_file_code(name, md.permissions().mode());
+        };
+
+        self.file_codes.get(key)
+    }
+
+    // executables come before extensions, the longest matching pattern wins
+    fn get_file_code(&self, name: &str, mode: u32) -> Option<&String> {
+        if mode & EXECUTABLE_BITS != 0 {
+            return self.file_codes.get("ex");
+        }
+
+        self.file_codes.iter()
+            .filter_map(|(key, code)| key.strip_prefix('*').map(|suffix| (suffix, code)))
+            .filter(|(suffix, _)| name.ends_with(suffix))
+            .max_by_key(|(suffix, _)| suffix.len())
+            .map(|(_, code)| code)
+            .or_else(|| self.file_codes.get("fi"))
+    }
+}
+
+/// The width of text in the terminal, not counting escape sequences.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+
+    while let Some(start) = rest.find('\x1b') {
+        width += rest[..start].width();
+
+        // escape sequences used here all end in a letter
+        rest = &rest[start..];
+        rest = match rest.find(|c: char| c.is_ascii_alphabetic()) {
+            Some(end) => &rest[end + 1..],
+            None => "",
+        };
+    }
+
+    width + rest.width()
+}
+
+// reads KEY=CODE entries separated by colons from the variables in order, so
+// later ones win, keeping the keys that are used for this kind of color
+fn read_codes(vars: &[&str], is_used: impl Fn(&str) -> bool,
+    codes: &mut HashMap<String, String>) {
+    for value in vars.iter().filter_map(|v| env::var(v).ok()) {
+        for entry in value.split(':') {
+            match entry.split_once('=') {
+                Some((key, code)) if is_used(key) => {
+                    codes.insert(key.to_string(), code.to_string());
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+fn paint_code(code: &str, text: &str) -> String {
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
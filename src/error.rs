@@ -0,0 +1,90 @@
+use std::{
+    fmt, io,
+    sync::atomic::{ AtomicI32, Ordering },
+};
+
+use crate::output::Output;
+
+/// Exit code for problems that only affect part of the output, like an
+/// unreadable directory found while recursing.
+pub const EXIT_MINOR: i32 = 1;
+/// Exit code for problems with the paths given on the command line and errors
+/// that stop eb altogether.
+pub const EXIT_SERIOUS: i32 = 2;
+
+static EXIT_CODE: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Debug)]
+pub enum Error {
+    /// A file or directory couldn't be accessed or read.
+    Path { path: String, source: io::Error },
+    /// Writing to stdout or the pager failed.
+    Output(io::Error),
+    /// Anything else that stops a file from being shown, like an unknown theme.
+    Other(String),
+}
+
+impl Error {
+    pub fn path(path: &str, source: io::Error) -> Error {
+        Error::Path { path: path.to_string(), source }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Path { path, source } => write!(f, "{}: {}", path, describe(source)),
+            Error::Output(e) => write!(f, "write error: {}", describe(e)),
+            Error::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+// anything written with ? is output, reading has to be mapped to a path error
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Output(e)
+    }
+}
+
+/// Prints an error to stderr and makes sure eb exits with at least the given
+/// exit code. Output held back so far is written first so that the error shows
+/// up after it.
+pub fn report(err: &Error, code: i32, out: &mut Output) {
+    // a write that fails here fails again with the next one
+    let _ = out.release();
+    eprintln!("eb: {}", err);
+    EXIT_CODE.fetch_max(code, Ordering::Relaxed);
+}
+
+/// Reports an error that only affects one path so that eb can carry on with
+/// the rest. Output errors are passed on since there's no point in continuing.
+pub fn handle(result: Result<(), Error>, code: i32, out: &mut Output) -> Result<(), Error> {
+    match result {
+        Err(Error::Output(e)) => Err(Error::Output(e)),
+        Err(e) => {
+            report(&e, code, out);
+            Ok(())
+        },
+        Ok(()) => Ok(()),
+    }
+}
+
+/// Like handle, for listing or printing a path given on the command line, where
+/// failing is serious.
+pub fn handle_given(result: Result<(), Error>, out: &mut Output) -> Result<(), Error> {
+    handle(result, EXIT_SERIOUS, out)
+}
+
+pub fn exit_code() -> i32 {
+    EXIT_CODE.load(Ordering::Relaxed)
+}
+
+// the message of an io error without the " (os error N)" at the end
+fn describe(e: &io::Error) -> String {
+    let msg = e.to_string();
+    match msg.find(" (os error") {
+        Some(i) => msg[..i].to_string(),
+        None => msg,
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    io::{ self, Write },
+    os::unix::fs::MetadataExt,
+    path::Path,
+    time::{ SystemTime, UNIX_EPOCH },
+};
+use uzers::UsersCache;
+
+use crate::{
+    Args,
+    color::Colors,
+    error::Error,
+    git::GitStatus,
+    list::{ self, ListEntry },
+    output::Output,
+    time::{ self, TimeStyle },
+};
+
+const PATH_LABEL: &str = "Path";
+// characters that make a csv field need quotes
+const CSV_SPECIAL: [char; 4] = [',', '"', '\n', '\r'];
+
+/// Lists the paths given, or the current directory, as rows of comma or tab
+/// separated values with the columns of the long view. Values are raw so that
+/// they sort: sizes in bytes and times in seconds since the epoch, or as ISO
+/// 8601 with a `--time-style` other than the default. Trees are flattened.
+pub fn list_table(out: &mut Output, args: &Args) -> Result<(), Error> {
+    let sep = match args.format.as_deref() {
+        Some("tsv") => '\t',
+        _ => ',',
+    };
+
+    let mut header: Vec<&str> = list::get_header_labels(args.git, args).iter()
+        .map(|(_, label)| *label)
+        .collect();
+    header.extend([list::NAME_LABEL, PATH_LABEL]);
+    write_row(&header, sep, out)?;
+
+    let users = UsersCache::new();
+    let colors = Colors::plain();
+
+    list::list_root_entries(out, args, |entries, git, out| {
+        for entry in entries {
+            write_entry(&entry, git, &users, &colors, sep, out, args)?;
+        }
+        Ok(())
+    })
+}
+
+// writes the row of an entry followed by the rows of its children in trees
+fn write_entry(entry: &ListEntry, git: Option<&GitStatus>, users: &UsersCache, colors: &Colors,
+    sep: char, out: &mut Output, args: &Args) -> io::Result<()> {
+    let md = &entry.md;
+
+    let mut row: Vec<String> = list::get_header_labels(args.git, args).iter()
+        .map(|(key, _)| match *key {
+            "inode" => md.ino().to_string(),
+            "octal" => format!("{:04o}", md.mode() & list::PERMISSION_BITS),
+            "permissions" => list::get_permissions(md, colors),
+            "links" => md.nlink().to_string(),
+            "size" => md.size().to_string(),
+            "blocks" => md.blocks().to_string(),
+            "user" => list::get_user_name(md.uid(), users, args),
+            "group" => list::get_group_name(md.gid(), users, args),
+            "modified" => format_time(md.modified(), args),
+            "changed" => format_time(time::get_changed(md), args),
+            "created" => format_time(md.created(), args),
+            "accessed" => format_time(md.accessed(), args),
+            "git" => git.and_then(|g| g.get(&entry.path))
+                .map(|(staged, unstaged)| format!("{}{}", staged, unstaged))
+                .unwrap_or_default(),
+            _ => String::new(),
+        })
+        .collect();
+
+    let name = match Path::new(&entry.path).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => entry.path.clone(),
+    };
+    row.extend([name, entry.path.clone()]);
+    write_row(&row, sep, out)?;
+
+    for child in entry.children.iter().flatten() {
+        write_entry(child, git, users, colors, sep, out, args)?;
+    }
+
+    Ok(())
+}
+
+// missing timestamps are left empty
+fn format_time(time: io::Result<SystemTime>, args: &Args) -> String {
+    match args.time_style {
+        TimeStyle::Default => time.ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs().to_string())
+            .unwrap_or_default(),
+        _ => time::format_rfc3339(time).unwrap_or_default(),
+    }
+}
+
+fn write_row(row: &[impl AsRef<str>], sep: char, out: &mut Output) -> io::Result<()> {
+    let fields: Vec<String> = row.iter()
+        .map(|f| escape_field(f.as_ref(), sep))
+        .collect();
+
+    writeln!(out, "{}", fields.join(&sep.to_string()))
+}
+
+// csv quotes fields and doubles the quotes in them, tsv has no quoting so
+// tabs and line breaks are written as escapes
+fn escape_field(field: &str, sep: char) -> String {
+    if sep == '\t' {
+        field.replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+    } else if field.contains(CSV_SPECIAL) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_leaves_plain_fields() {
+        assert_eq!(escape_field("file.txt", ','), "file.txt");
+        assert_eq!(escape_field("a\tb\\c", ','), "a\tb\\c");
+    }
+
+    #[test]
+    fn csv_quotes_special_fields() {
+        assert_eq!(escape_field("a,b", ','), "\"a,b\"");
+        assert_eq!(escape_field("a\nb", ','), "\"a\nb\"");
+        assert_eq!(escape_field("a\rb", ','), "\"a\rb\"");
+    }
+
+    #[test]
+    fn csv_doubles_quotes() {
+        assert_eq!(escape_field("say \"hi\"", ','), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn tsv_escapes_special_characters() {
+        assert_eq!(escape_field("a\tb", '\t'), "a\\tb");
+        assert_eq!(escape_field("a\nb\rc", '\t'), "a\\nb\\rc");
+        assert_eq!(escape_field("a\\tb", '\t'), "a\\\\tb");
+    }
+
+    #[test]
+    fn tsv_leaves_commas_and_quotes() {
+        assert_eq!(escape_field("a,\"b\"", '\t'), "a,\"b\"");
+    }
+}
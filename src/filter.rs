@@ -0,0 +1,51 @@
+use globset::{ Glob, GlobSet, GlobSetBuilder };
+
+use crate::{ Args, git::GitIgnore };
+
+const GLOB_SEP: char = '|';
+
+/// Parses the patterns given to `--ignore-glob`, separated by `|`. None if any
+/// of them isn't a valid glob.
+pub fn parse_globs(globs: &str) -> Option<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs.split(GLOB_SEP).filter(|g| !g.is_empty()) {
+        builder.add(Glob::new(glob).ok()?);
+    }
+
+    builder.build().ok()
+}
+
+/// Decides which directory entries are left out of listings: hidden files
+/// without `--all`, files git ignores with `--git-ignore` and names matching
+/// `--ignore-glob`. The same rules apply to recursion and trees.
+pub struct Filter<'a> {
+    show_hidden: bool,
+    git_ignore: Option<GitIgnore>,
+    globs: Option<&'a GlobSet>,
+}
+
+impl<'a> Filter<'a> {
+    /// Reads the ignore rules of the repository path is in, if they're used.
+    pub fn load(path: &str, args: &'a Args) -> Filter<'a> {
+        let git_ignore = if args.git_ignore {
+            GitIgnore::load(path)
+        } else {
+            None
+        };
+
+        Filter { show_hidden: args.all, git_ignore, globs: args.ignore_glob.as_ref() }
+    }
+
+    /// Whether the file at path, named name, is left out.
+    pub fn is_ignored(&self, path: &str, name: &str, is_dir: bool) -> bool {
+        if name.starts_with('.') && !self.show_hidden {
+            return true;
+        }
+
+        if self.globs.is_some_and(|g| g.is_match(name)) {
+            return true;
+        }
+
+        self.git_ignore.as_ref().is_some_and(|g| g.is_ignored(path, is_dir))
+    }
+}
@@ -0,0 +1,299 @@
+use git2::{ DiffOptions, Repository, Status, StatusOptions };
+use std::{ cmp::Reverse, collections::HashMap, fs, path::{ Path, PathBuf } };
+
+use crate::color::Colors;
+
+// the status characters from the most important down, which is what a
+// directory shows for its contents
+const STATUS_RANKS: [char; 8] = ['U', 'D', 'R', 'T', 'M', 'N', 'I', '-'];
+const UNCHANGED: char = '-';
+const GIT_DIR: &str = ".git";
+
+/// The status of the files in git repositories compared to the index and HEAD,
+/// read once for a whole listing.
+pub struct GitStatus {
+    // nested repositories come before the ones they're in
+    repos: Vec<RepoStatus>,
+}
+
+struct RepoStatus {
+    workdir: PathBuf,
+    // paths relative to the work directory, untracked and ignored directories
+    // that weren't looked into end in a slash
+    statuses: Vec<(String, Status)>,
+}
+
+impl GitStatus {
+    /// Finds the repository path is in, if any, and reads the status of its files.
+    pub fn load(path: &str) -> Option<GitStatus> {
+        GitStatus::load_all(&[path.to_string()])
+    }
+
+    /// Finds the repositories the paths are in and reads the status of their
+    /// files, once for each repository. None if none of them is in one.
+    pub fn load_all(paths: &[String]) -> Option<GitStatus> {
+        let mut repos: Vec<RepoStatus> = Vec::new();
+        for path in paths {
+            let repo = match Repository::discover(path) {
+                Ok(x) => x,
+                Err(_) => continue,
+            };
+            let workdir = match repo.workdir().and_then(|w| fs::canonicalize(w).ok()) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            if repos.iter().any(|r| r.workdir == workdir) {
+                continue;
+            }
+            if let Some(statuses) = read_statuses(&repo) {
+                repos.push(RepoStatus { workdir, statuses });
+            }
+        }
+
+        if repos.is_empty() {
+            return None;
+        }
+
+        repos.sort_by_key(|r| Reverse(r.workdir.components().count()));
+        Some(GitStatus { repos })
+    }
+
+    /// The staged and unstaged status of a file, or the most important ones of
+    /// everything in it for directories. None for files outside the repositories.
+    pub fn get(&self, path: &str) -> Option<(char, char)> {
+        let (repo, rel_path) = self.repos.iter()
+            .find_map(|r| get_relative_path(&r.workdir, path).map(|p| (r, p)))?;
+        let dir_prefix = format!("{}/", rel_path);
+
+        let mut result = (UNCHANGED, UNCHANGED);
+        for (entry_path, status) in &repo.statuses {
+            // the file itself or an untracked or ignored directory it's in
+            let is_self = *entry_path == rel_path
+                || *entry_path == dir_prefix
+                || (entry_path.ends_with('/') && rel_path.starts_with(entry_path.as_str()));
+            // something in a directory, where ignored files don't count since
+            // most directories have some
+            let is_contents = (rel_path.is_empty() || entry_path.starts_with(&dir_prefix))
+                && !status.is_ignored();
+
+            if is_self || is_contents {
+                result = (
+                    most_important(result.0, get_staged_char(*status)),
+                    most_important(result.1, get_unstaged_char(*status)),
+                );
+            }
+        }
+
+        Some(result)
+    }
+}
+
+// renamed files are listed under their new name
+fn read_statuses(repo: &Repository) -> Option<Vec<(String, Status)>> {
+    let mut options = StatusOptions::new();
+    options.include_untracked(true)
+        .include_ignored(true)
+        .renames_head_to_index(true);
+
+    let statuses = repo.statuses(Some(&mut options)).ok()?
+        .iter()
+        .filter_map(|e| {
+            let new_path = e.index_to_workdir()
+                .or_else(|| e.head_to_index())
+                .and_then(|d| d.new_file().path().map(|p| p.to_string_lossy().into_owned()));
+            new_path.or_else(|| e.path().map(String::from)).map(|p| (p, e.status()))
+        })
+        .collect();
+
+    Some(statuses)
+}
+
+/// The ignore rules of a git repository, from `.gitignore` files,
+/// `.git/info/exclude` and the global excludes file.
+pub struct GitIgnore {
+    repo: Repository,
+    workdir: PathBuf,
+}
+
+impl GitIgnore {
+    /// Finds the repository path is in, if any.
+    pub fn load(path: &str) -> Option<GitIgnore> {
+        let repo = Repository::discover(path).ok()?;
+        let workdir = fs::canonicalize(repo.workdir()?).ok()?;
+
+        Some(GitIgnore { repo, workdir })
+    }
+
+    /// Whether a file is ignored. Files outside the repository never are and
+    /// neither is the .git directory, which git doesn't list anyway.
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let rel_path = match get_relative_path(&self.workdir, path) {
+            Some(p) if !p.is_empty() => p,
+            _ => return false,
+        };
+
+        // libgit2 counts the .git directory and everything in it as ignored
+        if Path::new(&rel_path).starts_with(GIT_DIR) {
+            return false;
+        }
+
+        // patterns ending in a slash only match directories
+        let rel_path = if is_dir {
+            format!("{}/", rel_path)
+        } else {
+            rel_path
+        };
+
+        self.repo.is_path_ignored(rel_path).unwrap_or(false)
+    }
+}
+
+// the path within the work directory, with links resolved everywhere but in
+// the file name so that links are looked up themselves
+fn get_relative_path(workdir: &Path, path: &str) -> Option<String> {
+    let path = Path::new(path);
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+
+    let abs_path = match path.file_name() {
+        Some(name) => fs::canonicalize(parent).ok()?.join(name),
+        None => fs::canonicalize(path).ok()?,
+    };
+
+    let rel_path = abs_path.strip_prefix(workdir).ok()?;
+    Some(rel_path.to_string_lossy().into_owned())
+}
+
+/// How a line of a file differs from the index, shown in the gutter when
+/// printing files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines were deleted before the first line.
+    RemovedAbove,
+    /// Lines were deleted right after this one.
+    RemovedBelow,
+}
+
+/// The lines of a file changed since it was last staged, by line number from 1.
+/// None for files outside a repository, untracked files have no changes.
+pub fn get_line_changes(path: &str) -> Option<HashMap<usize, LineChange>> {
+    let repo = Repository::discover(path).ok()?;
+    let workdir = fs::canonicalize(repo.workdir()?).ok()?;
+    let rel_path = get_relative_path(&workdir, path)?;
+
+    let mut options = DiffOptions::new();
+    options.pathspec(&rel_path)
+        .disable_pathspec_match(true)
+        .context_lines(0);
+    let diff = repo.diff_index_to_workdir(None, Some(&mut options)).ok()?;
+
+    let mut changes = HashMap::new();
+    let mut add_hunk = |_: git2::DiffDelta, hunk: git2::DiffHunk| {
+        let new_start = hunk.new_start() as usize;
+        let new_end = new_start + hunk.new_lines() as usize;
+
+        if hunk.old_lines() == 0 {
+            for line in new_start..new_end {
+                changes.insert(line, LineChange::Added);
+            }
+        } else if hunk.new_lines() == 0 {
+            // deletions are shown on the line before them
+            if new_start == 0 {
+                changes.insert(1, LineChange::RemovedAbove);
+            } else {
+                changes.insert(new_start, LineChange::RemovedBelow);
+            }
+        } else {
+            for line in new_start..new_end {
+                changes.insert(line, LineChange::Modified);
+            }
+        }
+
+        true
+    };
+    diff.foreach(&mut |_, _| true, None, Some(&mut add_hunk), None).ok()?;
+
+    Some(changes)
+}
+
+/// The two status characters of a file colored by what they mean.
+pub fn paint_status(status: (char, char), colors: &Colors) -> String {
+    paint_char(status.0, colors) + &paint_char(status.1, colors)
+}
+
+/// A single character for grids, the unstaged status if there is one and the
+/// staged one otherwise.
+pub fn paint_marker(status: (char, char), colors: &Colors) -> String {
+    match status {
+        (UNCHANGED, UNCHANGED) => String::from(" "),
+        (staged, UNCHANGED) => paint_char(staged, colors),
+        (_, unstaged) => paint_char(unstaged, colors),
+    }
+}
+
+fn paint_char(c: char, colors: &Colors) -> String {
+    let key = match c {
+        'N' => "ga",
+        'M' => "gm",
+        'D' => "gd",
+        'R' => "gv",
+        'T' => "gt",
+        'I' => "gi",
+        'U' => "gc",
+        _ => "xx",
+    };
+
+    colors.paint(key, &c.to_string())
+}
+
+fn get_staged_char(status: Status) -> char {
+    if status.is_conflicted() {
+        'U'
+    } else if status.is_index_new() {
+        'N'
+    } else if status.is_index_modified() {
+        'M'
+    } else if status.is_index_deleted() {
+        'D'
+    } else if status.is_index_renamed() {
+        'R'
+    } else if status.is_index_typechange() {
+        'T'
+    } else {
+        UNCHANGED
+    }
+}
+
+fn get_unstaged_char(status: Status) -> char {
+    if status.is_conflicted() {
+        'U'
+    } else if status.is_wt_new() {
+        'N'
+    } else if status.is_wt_modified() {
+        'M'
+    } else if status.is_wt_deleted() {
+        'D'
+    } else if status.is_wt_renamed() {
+        'R'
+    } else if status.is_wt_typechange() {
+        'T'
+    } else if status.is_ignored() {
+        'I'
+    } else {
+        UNCHANGED
+    }
+}
+
+fn most_important(a: char, b: char) -> char {
+    let rank = |c| STATUS_RANKS.iter().position(|&r| r == c).unwrap_or(STATUS_RANKS.len());
+    if rank(a) <= rank(b) {
+        a
+    } else {
+        b
+    }
+}
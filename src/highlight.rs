@@ -0,0 +1,183 @@
+use std::{ env, path::{ Path, PathBuf }, sync::OnceLock };
+use syntect::{
+    highlighting::{ Color, FontStyle, Style, Theme, ThemeSet },
+    parsing::{ SyntaxReference, SyntaxSet },
+};
+
+use crate::{ Args, error::{ self, Error }, output::Output };
+
+const DEFAULT_THEME: &str = "base16-eighties.dark";
+const CONFIG_DIR_VAR: &str = "EB_CONFIG_DIR";
+const SYNTAXES_DIR: &str = "syntaxes";
+const THEMES_DIR: &str = "themes";
+// vim only looks for modelines in the first few lines of a file
+pub const MODELINE_LINES: usize = 5;
+
+// loaded the first time a file is highlighted and kept for the rest
+static ASSETS: OnceLock<Assets> = OnceLock::new();
+
+/// Syntax definitions and themes used for highlighting.
+///
+/// The defaults shipped with syntect are compiled into the binary. Extra
+/// `.sublime-syntax` and `.tmTheme` files are picked up from the `syntaxes` and
+/// `themes` folders of the eb config directory.
+pub struct Assets {
+    pub syntax_set: SyntaxSet,
+    pub theme_set: ThemeSet,
+}
+
+impl Assets {
+    /// The assets shared by every file printed, loaded on first use.
+    pub fn get(out: &mut Output) -> &'static Assets {
+        ASSETS.get_or_init(|| Assets::load(out))
+    }
+
+    fn load(out: &mut Output) -> Assets {
+        let mut syntax_set = SyntaxSet::load_defaults_newlines();
+        let mut theme_set = ThemeSet::load_defaults();
+
+        if let Some(dir) = config_dir() {
+            let syntaxes = dir.join(SYNTAXES_DIR);
+            if syntaxes.is_dir() {
+                let mut builder = syntax_set.into_builder();
+                if let Err(e) = builder.add_from_folder(&syntaxes, true) {
+                    let msg = format!("{}: failed to load syntaxes: {}", syntaxes.display(), e);
+                    error::report(&Error::Other(msg), error::EXIT_MINOR, out);
+                }
+                syntax_set = builder.build();
+            }
+
+            let themes = dir.join(THEMES_DIR);
+            if themes.is_dir() {
+                if let Err(e) = theme_set.add_from_folder(&themes) {
+                    let msg = format!("{}: failed to load themes: {}", themes.display(), e);
+                    error::report(&Error::Other(msg), error::EXIT_MINOR, out);
+                }
+            }
+        }
+
+        Assets { syntax_set, theme_set }
+    }
+
+    /// Picks a syntax by (in order of priority) the `--language` option, a vim or
+    /// emacs modeline, the file name or extension and finally the shebang line.
+    pub fn get_syntax(&self, path: &str, lines: &[String], args: &Args)
+        -> Result<&SyntaxReference, Error> {
+        if !args.language.is_empty() {
+            return match self.syntax_set.find_syntax_by_token(&args.language) {
+                Some(s) => Ok(s),
+                None => Err(Error::Other(format!("unknown language: {}", args.language))),
+            };
+        }
+
+        let modeline_syntax = lines.iter()
+            .take(MODELINE_LINES)
+            .filter_map(|l| get_modeline_language(l))
+            .find_map(|lang| self.syntax_set.find_syntax_by_token(&lang));
+        if let Some(s) = modeline_syntax {
+            return Ok(s);
+        }
+
+        let path = Path::new(path);
+        let by_name = path.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| self.syntax_set.find_syntax_by_extension(n));
+        let by_ext = path.extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| self.syntax_set.find_syntax_by_extension(e));
+        if let Some(s) = by_name.or(by_ext) {
+            return Ok(s);
+        }
+
+        Ok(lines.first()
+            .and_then(|l| self.syntax_set.find_syntax_by_first_line(l))
+            .unwrap_or_else(|| self.syntax_set.find_syntax_plain_text()))
+    }
+
+    pub fn get_theme(&self, args: &Args) -> Result<&Theme, Error> {
+        let name = if args.theme.is_empty() {
+            DEFAULT_THEME
+        } else {
+            &args.theme
+        };
+
+        match self.theme_set.themes.get(name) {
+            Some(t) => Ok(t),
+            None => Err(Error::Other(format!("unknown theme: {}", name))),
+        }
+    }
+}
+
+/// Wraps text in the ANSI escape sequences for a highlighting style.
+pub fn style_text(style: &Style, text: &str) -> String {
+    let mut result = String::new();
+
+    if style.font_style.contains(FontStyle::BOLD) {
+        result.push_str("\x1b[1m");
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        result.push_str("\x1b[3m");
+    }
+    if style.font_style.contains(FontStyle::UNDERLINE) {
+        result.push_str("\x1b[4m");
+    }
+    result.push_str(&fg_escape(style.foreground));
+    result.push_str(text);
+    result.push_str("\x1b[0m");
+
+    result
+}
+
+fn fg_escape(color: Color) -> String {
+    format!("\x1b[38;2;{};{};{}m", color.r, color.g, color.b)
+}
+
+// finds the language set by a modeline such as `vim: set ft=rust:` or
+// `-*- mode: python -*-`
+fn get_modeline_language(line: &str) -> Option<String> {
+    if let Some(start) = line.find("-*-") {
+        let rest = &line[start + 3..];
+        let end = rest.find("-*-")?;
+
+        for var in rest[..end].split(';') {
+            match var.split_once(':') {
+                Some((key, value)) if key.trim().eq_ignore_ascii_case("mode") => {
+                    return Some(value.trim().to_string());
+                },
+                // `-*- python -*-` is shorthand for the mode
+                None if !var.trim().is_empty() => return Some(var.trim().to_string()),
+                _ => {},
+            }
+        }
+    }
+
+    for marker in ["vim:", "vi:", "ex:"] {
+        // the marker has to start the line or follow whitespace
+        let start = line.match_indices(marker)
+            .map(|(i, _)| i)
+            .find(|&i| i == 0 || line[..i].ends_with(char::is_whitespace));
+
+        if let Some(start) = start {
+            for option in line[start + marker.len()..].split([' ', ':', '\t']) {
+                if let Some((key, value)) = option.split_once('=') {
+                    if ["ft", "filetype", "syntax", "syn"].contains(&key) {
+                        return Some(value.trim().to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn config_dir() -> Option<PathBuf> {
+    if let Some(dir) = env::var_os(CONFIG_DIR_VAR) {
+        return Some(PathBuf::from(dir));
+    }
+
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) => Some(PathBuf::from(dir).join("eb")),
+        None => env::var_os("HOME").map(|h| PathBuf::from(h).join(".config").join("eb")),
+    }
+}
@@ -0,0 +1,119 @@
+use serde_json::{ json, Value };
+use std::{
+    fs::{ Metadata, read_link },
+    io::Write,
+    os::unix::fs::{ FileTypeExt, MetadataExt },
+    path::Path,
+};
+use uzers::{ Groups, Users, UsersCache };
+
+use crate::{
+    Args,
+    error::Error,
+    git::GitStatus,
+    list::{ self, ListEntry },
+    output::Output,
+    time,
+};
+
+const CHILDREN_KEY: &str = "children";
+
+/// Lists the paths given, or the current directory, as a JSON array of entries
+/// with `--json` or one entry per line with `--ndjson`. Directories are listed
+/// by their contents, which trees nest in the children of each directory.
+pub fn list_json(out: &mut Output, args: &Args) -> Result<(), Error> {
+    let users = UsersCache::new();
+    let mut entries = Vec::new();
+
+    list::list_root_entries(out, args, |list_entries, git, _| {
+        entries.extend(list_entries.into_iter().map(|e| get_entry(e, git, &users)));
+        Ok(())
+    })?;
+
+    if args.ndjson {
+        for entry in entries {
+            write_ndjson(entry, out)?;
+        }
+    } else {
+        writeln!(out, "{:#}", Value::Array(entries))?;
+    }
+
+    Ok(())
+}
+
+// everything the long view shows about a file, with raw values
+fn get_entry(entry: ListEntry, git: Option<&GitStatus>, users: &UsersCache) -> Value {
+    let (path, md) = (entry.path.as_str(), &entry.md);
+    let name = match Path::new(path).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => path.to_string(),
+    };
+
+    let mut value = json!({
+        "path": path,
+        "name": name,
+        "type": get_type_name(md),
+        "size": md.size(),
+        "blocks": md.blocks(),
+        "inode": md.ino(),
+        "links": md.nlink(),
+        "mode": format!("{:04o}", md.mode() & list::PERMISSION_BITS),
+        "uid": md.uid(),
+        "user": users.get_user_by_uid(md.uid())
+            .map(|u| u.name().to_string_lossy().into_owned()),
+        "gid": md.gid(),
+        "group": users.get_group_by_gid(md.gid())
+            .map(|g| g.name().to_string_lossy().into_owned()),
+        "modified": time::format_rfc3339(md.modified()),
+        "accessed": time::format_rfc3339(md.accessed()),
+        "changed": time::format_rfc3339(time::get_changed(md)),
+        "created": time::format_rfc3339(md.created()),
+        "target": read_link(path).ok().map(|t| t.to_string_lossy().into_owned()),
+    });
+
+    if let Some(git) = git {
+        value["git"] = json!(git.get(path).map(|(staged, unstaged)| format!("{}{}", staged,
+            unstaged)));
+    }
+
+    if let Some(children) = entry.children {
+        let children = children.into_iter().map(|c| get_entry(c, git, users)).collect();
+        value[CHILDREN_KEY] = Value::Array(children);
+    }
+
+    value
+}
+
+fn get_type_name(md: &Metadata) -> &'static str {
+    let file_type = md.file_type();
+
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block_device"
+    } else if file_type.is_char_device() {
+        "char_device"
+    } else {
+        "file"
+    }
+}
+
+// one entry per line, so the children of trees follow their directory
+fn write_ndjson(mut entry: Value, out: &mut Output) -> Result<(), Error> {
+    let children = entry.as_object_mut().and_then(|e| e.remove(CHILDREN_KEY));
+    writeln!(out, "{}", entry)?;
+
+    if let Some(Value::Array(children)) = children {
+        for child in children {
+            write_ndjson(child, out)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,105 +1,470 @@
 use bitvec::prelude::*;
-use chrono::{ DateTime, offset::Local };
 use std::{
     collections::HashMap,
-    fs::{ DirEntry, metadata, read_dir },
-    os::{ unix::fs::{ MetadataExt, PermissionsExt } },
-    time::{ Duration, SystemTime },
+    fs::{ DirEntry, Metadata, metadata, read_dir, read_link, symlink_metadata },
+    io::{ self, IsTerminal, Write },
+    path::Path,
+    os::{ unix::fs::{ FileTypeExt, MetadataExt, PermissionsExt } },
 };
 use term_grid::{ Alignment, Cell, Direction, Filling, Grid, GridOptions };
-use term_size;
-
-use crate::{ Args, sort };
+use uzers::{ Groups, Users, UsersCache };
+
+use crate::{
+    Args,
+    color::{ self, Colors },
+    error::{ self, Error },
+    filter::Filter,
+    git::{ self, GitStatus },
+    output::{ self, Output },
+    sort,
+    time,
+    tree::TreeParts,
+};
 
 const PERM_CHARS: [char; 3] = ['r', 'w', 'x'];
 const NO_PERM: char = '-';
+// the color keys of the user, group and others' permissions
+const PERM_CLASSES: [char; 3] = ['u', 'g', 't'];
+// the color keys of sizes from bytes up, with the last one for anything bigger
+const SIZE_KEYS: [&str; 5] = ["nb", "nk", "nm", "ng", "nt"];
+// the color key of the dashes shown for missing permissions and sizes
+const NO_VALUE_KEY: &str = "xx";
+// a type character and three sets of rwx
+const PERMISSIONS_WIDTH: usize = 10;
+// setuid, setgid and sticky, which show up in place of the x of each set
+const SPECIAL_BITS: [u32; 3] = [0o4000, 0o2000, 0o1000];
+const SPECIAL_CHARS: [char; 3] = ['s', 's', 't'];
+pub const PERMISSION_BITS: u32 = 0o7777;
+const OCTAL_WIDTH: usize = 4;
+// like ls, ACLs are marked with + and other extended attributes with @
+const ACL_ATTRIBUTE: &str = "system.posix_acl_access";
+const ACL_MARKER: char = '+';
+const XATTR_MARKER: char = '@';
+pub const NAME_LABEL: &str = "Name";
+const LINK_ARROW: &str = "->";
+const NO_GIT_STATUS: char = '-';
+// the staged and unstaged status are always one character each
+const GIT_STATUS_PLAIN: &str = "--";
 const PREFIXES: [char; 6] = ['k', 'M', 'G', 'T', 'P', 'E'];
 const BINARY_PREFIX: char = 'i';
 const DIRECTORY_SIZE: &str = "-";
-const TIME_FORMAT: &str = "%d %b %H:%M";
 const GRID_MARGIN: usize = 2;
 const LONG_GRID_MARGIN: usize = 4;
-
-pub fn list_dirs(args: &Args) {
-    let paths = match args.paths.len() {
-        0 => {
-            vec![String::from(".")]      
-        },
-        _ => {
-            args.paths.clone()
+// grids asked for without a terminal are fit into the traditional width
+const DEFAULT_WIDTH: usize = 80;
+// shown in trees after a directory that leads back to one of its ancestors
+const LOOP_NOTICE: &str = "[recursive, not followed]";
+// shown after the target of a broken link, for when there are no colors
+const BROKEN_LINK_NOTICE: &str = "[broken]";
+
+pub fn list_dirs(out: &mut Output, args: &Args) -> Result<(), Error> {
+    // leave out the paths that don't exist
+    let mut paths = Vec::new();
+    for path in get_root_paths(args) {
+        match get_metadata(&path, args) {
+            Ok(_) => paths.push(path),
+            Err(e) => error::handle_given(Err(Error::path(&path, e)), out)?,
         }
+    }
+
+    let colors = Colors::load(args);
+    // the paths can be in different repositories
+    let git = if args.git {
+        GitStatus::load_all(&paths)
+    } else {
+        None
     };
 
     if args.long {
-        let items = get_long_form_items(&paths, args);
+        let (header, items) = get_long_form_items(&paths, &colors, git.as_ref(), out, args);
+
+        if let Some(header) = header {
+            writeln!(out, "{}", header)?;
+        }
 
         if args.grid {
-            list_in_grid(items, LONG_GRID_MARGIN, args);
+            list_in_grid(items, LONG_GRID_MARGIN, out, args)
         } else {
-            list_one_per_line(items);
+            list_one_per_line(items, out)
         }
     } else {
-        let items = get_short_form_items(&paths);
+        let items = get_short_form_items(&paths, &colors, git.as_ref());
 
         if args.oneline {
-            list_one_per_line(items);
+            list_one_per_line(items, out)
         } else {
-            list_in_grid(items, GRID_MARGIN, args);
+            list_in_grid(items, GRID_MARGIN, out, args)
         }
     }
 }
 
-pub fn list_dir_contents(path: &str, args: &Args) {
-    // get files
-    let mut files = Vec::new();
-    for r in read_dir(path).unwrap() {
-        files.push(r.unwrap());
+pub fn list_dir_contents(path: &str, out: &mut Output, args: &Args) -> Result<(), Error> {
+    if args.tree {
+        return list_tree(path, out, args);
     }
 
-    sort::sort_files(&mut files, args);
+    let md = metadata(path).map_err(|e| Error::path(path, e))?;
+    let git = load_git(path, args);
+    let filter = Filter::load(path, args);
+    list_recursive(path, &mut vec![file_id(&md)], &Colors::load(args), git.as_ref(), &filter, out,
+        args)
+}
+
+/// The paths given on the command line, or the current directory.
+pub fn get_root_paths(args: &Args) -> Vec<String> {
+    if args.paths.is_empty() {
+        vec![String::from(".")]
+    } else {
+        args.paths.clone()
+    }
+}
+
+/// The status of the repository path is in, only read with `--git`.
+pub fn load_git(path: &str, args: &Args) -> Option<GitStatus> {
+    if args.git {
+        GitStatus::load(path)
+    } else {
+        None
+    }
+}
+
+// lists a directory and, with -R, its subdirectories. ancestors works like in
+// recurse_into
+fn list_recursive(path: &str, ancestors: &mut Vec<(u64, u64)>, colors: &Colors,
+    git: Option<&GitStatus>, filter: &Filter, out: &mut Output, args: &Args)
+    -> Result<(), Error> {
+    let files = read_files(path, out, args)?;
 
     if args.long {
-        let items = get_long_form_items(
-            &get_file_paths(&files, path, args),
+        let (header, items) = get_long_form_items(
+            &get_file_paths(&files, path, filter, args),
+            colors,
+            git,
+            out,
             args,
         );
 
+        // every directory gets its own header in recursive listings
+        if let Some(header) = header {
+            writeln!(out, "{}", header)?;
+        }
+
         if args.grid {
-            list_in_grid(items, LONG_GRID_MARGIN, args);
+            list_in_grid(items, LONG_GRID_MARGIN, out, args)?;
         } else {
-            list_one_per_line(items);
+            list_one_per_line(items, out)?;
         }
     } else {
-        let items = get_short_form_items(&get_file_paths(&files, path,
-            args));
+        let items = get_short_form_items(&get_file_paths(&files, path, filter,
+            args), colors, git);
 
         if args.oneline {
-            list_one_per_line(items);
+            list_one_per_line(items, out)?;
         } else {
-            list_in_grid(items, GRID_MARGIN, args);
+            list_in_grid(items, GRID_MARGIN, out, args)?;
         }
     }
 
-    if args.recurse {
-        for file in files {
-            let md = match file.metadata() {
-                Ok(x) => x,
-                Err(e) => panic!("Failed to retrieve metadata: {}", e),
-            };
+    if args.recurse && can_recurse(ancestors, args) {
+        for (new_path, md) in get_subdirs(&files, path, filter, out, args) {
+            writeln!(out, "\n{}:", new_path)?;
+            recurse_into(&new_path, &md, ancestors, out,
+                |a, out| list_recursive(&new_path, a, colors, git, filter, out, args))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the directory and everything below it as a tree, down to the depth
+/// set with `--level`.
+pub fn list_tree(path: &str, out: &mut Output, args: &Args) -> Result<(), Error> {
+    let filter = Filter::load(path, args);
+    let mut nodes = Vec::new();
+    for entry in get_list_entries(path, &filter, out, args)? {
+        add_tree_nodes(entry, Vec::new(), &mut nodes);
+    }
+
+    let parts = TreeParts::get();
+    let colors = Colors::load(args);
+
+    // the metadata columns line up across the whole tree
+    let columns = if args.long {
+        let entries: Vec<(String, Metadata)> = nodes.iter()
+            .map(|n| (n.path.clone(), n.md.clone()))
+            .collect();
+        let git = load_git(path, args);
+        let (header, columns) = get_long_columns(&entries, &colors, git.as_ref(), args);
+
+        if let Some(header) = header {
+            writeln!(out, "{}{}", header, colors.paint("hd", NAME_LABEL))?;
+        }
+
+        columns
+    } else {
+        vec![String::new(); nodes.len()]
+    };
+
+    for (node, columns) in nodes.iter().zip(columns) {
+        let name = if args.long {
+            get_long_name(&node.path, &node.name, &node.md, &colors)
+        } else {
+            colors.paint_name(&node.path, &node.name)
+        };
+        write!(out, "{}{}{}", columns, colors.paint("tb", &parts.prefix(&node.lasts)), name)?;
+
+        if node.is_loop {
+            write!(out, " {}", LOOP_NOTICE)?;
+        }
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+struct TreeNode {
+    path: String,
+    md: Metadata,
+    name: String,
+    // whether this and each of its ancestors is the last item in its directory
+    lasts: Vec<bool>,
+    is_loop: bool,
+}
+
+// adds an entry to the tree, followed by its contents. the root is shown by
+// the path it was given as
+fn add_tree_nodes(entry: ListEntry, lasts: Vec<bool>, nodes: &mut Vec<TreeNode>) {
+    let name = if lasts.is_empty() {
+        entry.path.clone()
+    } else {
+        entry.path.split('/').next_back().unwrap().to_string()
+    };
+
+    let children = entry.children.unwrap_or_default();
+    let count = children.len();
+
+    nodes.push(TreeNode {
+        path: entry.path,
+        md: entry.md,
+        name,
+        lasts: lasts.clone(),
+        is_loop: entry.is_loop,
+    });
+
+    for (i, child) in children.into_iter().enumerate() {
+        let mut child_lasts = lasts.clone();
+        child_lasts.push(i == count - 1);
+        add_tree_nodes(child, child_lasts, nodes);
+    }
+}
+
+/// A file found while listing, with the contents of directories in trees.
+pub struct ListEntry {
+    pub path: String,
+    pub md: Metadata,
+    /// Whether this is a directory that leads back to one of its ancestors.
+    pub is_loop: bool,
+    pub children: Option<Vec<ListEntry>>,
+}
+
+/// The entries listed for a path given on the command line: files and, with
+/// `--list-dirs`, directories themselves, otherwise the contents of the
+/// directory. Trees have the directory with its contents as children and `-R`
+/// adds the contents of each subdirectory after its parent's.
+pub fn get_list_entries(path: &str, filter: &Filter, out: &mut Output, args: &Args)
+    -> Result<Vec<ListEntry>, Error> {
+    let md = metadata(path).map_err(|e| Error::path(path, e))?;
+    if !md.is_dir() || args.list_dirs {
+        return Ok(vec![ListEntry { path: path.to_string(), md, is_loop: false, children: None }]);
+    }
+
+    let mut ancestors = vec![file_id(&md)];
+    let mut entries = Vec::new();
+
+    if !args.tree {
+        add_list_entries(path, &mut ancestors, &mut entries, filter, out, args)?;
+        return Ok(entries);
+    }
+
+    // the root is there even if its contents can't be read
+    if args.level != 0 {
+        let result = add_list_entries(path, &mut ancestors, &mut entries, filter, out, args);
+        error::handle_given(result, out)?;
+    }
+
+    Ok(vec![ListEntry { path: path.to_string(), md, is_loop: false, children: Some(entries) }])
+}
+
+/// Gets the entries of each path given on the command line, or the current
+/// directory, and hands them to list along with the git status of the path.
+/// A path that fails is reported and the rest are still listed.
+pub fn list_root_entries(out: &mut Output, args: &Args,
+    mut list: impl FnMut(Vec<ListEntry>, Option<&GitStatus>, &mut Output) -> Result<(), Error>)
+    -> Result<(), Error> {
+    for path in get_root_paths(args) {
+        let filter = Filter::load(&path, args);
+        let git = load_git(&path, args);
+
+        let result = get_list_entries(&path, &filter, out, args)
+            .and_then(|entries| list(entries, git.as_ref(), out));
+        error::handle_given(result, out)?;
+    }
+
+    Ok(())
+}
 
-            if md.is_dir() {
-                let new_path = format!("{}/{}", path,
-                    file.file_name().into_string().unwrap());
+// adds the entries of a directory. trees nest the contents of subdirectories
+// in their entries, -R adds them after the directory's own
+fn add_list_entries(path: &str, ancestors: &mut Vec<(u64, u64)>, entries: &mut Vec<ListEntry>,
+    filter: &Filter, out: &mut Output, args: &Args) -> Result<(), Error> {
+    let files = read_files(path, out, args)?;
+    let can_recurse = can_recurse(ancestors, args);
 
-                println!("\n{}:", new_path);
+    for file_path in get_file_paths(&files, path, filter, args) {
+        let md = match get_metadata(&file_path, args) {
+            Ok(x) => x,
+            Err(e) => {
+                error::report(&Error::path(&file_path, e), error::EXIT_MINOR, out);
+                continue;
+            },
+        };
 
-                list_dir_contents(&new_path, args);
+        let mut entry = ListEntry { path: file_path, md, is_loop: false, children: None };
+
+        // an unreadable subdirectory shows up in the tree without contents
+        if args.tree && can_recurse && entry.md.is_dir() {
+            let mut children = Vec::new();
+            entry.is_loop = recurse_into(&entry.path, &entry.md, ancestors, out,
+                |a, out| add_list_entries(&entry.path, a, &mut children, filter, out, args))?;
+            if !entry.is_loop {
+                entry.children = Some(children);
             }
         }
+
+        entries.push(entry);
+    }
+
+    if args.recurse && !args.tree && can_recurse {
+        for (new_path, md) in get_subdirs(&files, path, filter, out, args) {
+            recurse_into(&new_path, &md, ancestors, out,
+                |a, out| add_list_entries(&new_path, a, entries, filter, out, args))?;
+        }
+    }
+
+    Ok(())
+}
+
+// whether the subdirectories of a directory are listed too, going by --level.
+// the root has depth 0 so its subdirectories are on level 1
+fn can_recurse(ancestors: &[(u64, u64)], args: &Args) -> bool {
+    let depth = ancestors.len() as isize - 1;
+    args.level < 0 || depth + 1 < args.level
+}
+
+// the subdirectories -R lists after a directory. directories that aren't
+// listed because they're hidden or ignored aren't recursed into either, but
+// ones left out with --only-files are
+fn get_subdirs(files: &[DirEntry], base: &str, filter: &Filter, out: &mut Output, args: &Args)
+    -> Vec<(String, Metadata)> {
+    let mut dirs = Vec::new();
+    for file in files {
+        let name = file.file_name().to_string_lossy().into_owned();
+        let path = format!("{}/{}", base, name);
+
+        if filter.is_ignored(&path, &name, is_real_dir(file)) {
+            continue;
+        }
+
+        match get_metadata(&path, args) {
+            Ok(md) if md.is_dir() => dirs.push((path, md)),
+            Ok(_) => {},
+            Err(e) => error::report(&Error::path(&path, e), error::EXIT_MINOR, out),
+        }
+    }
+    dirs
+}
+
+// lists a subdirectory with list, unless it leads back to one of the
+// directories being listed. ancestors holds those from the root down so that
+// symlinks leading back up aren't followed forever. true if a loop was cut
+// here, which is reported like ls does. an unreadable subdirectory doesn't
+// stop the rest of the listing
+fn recurse_into(path: &str, md: &Metadata, ancestors: &mut Vec<(u64, u64)>, out: &mut Output,
+    list: impl FnOnce(&mut Vec<(u64, u64)>, &mut Output) -> Result<(), Error>)
+    -> Result<bool, Error> {
+    let id = file_id(md);
+    if ancestors.contains(&id) {
+        let msg = format!("{}: not listing already-listed directory", path);
+        error::report(&Error::Other(msg), error::EXIT_MINOR, out);
+        return Ok(true);
+    }
+
+    ancestors.push(id);
+    let result = list(ancestors, out);
+    ancestors.pop();
+    error::handle(result, error::EXIT_MINOR, out)?;
+
+    Ok(false)
+}
+
+/// Gets the metadata of the file itself rather than what it links to, unless
+/// links are followed with `--dereference`.
+pub fn get_metadata(path: impl AsRef<Path>, args: &Args) -> io::Result<Metadata> {
+    if args.dereference {
+        metadata(path)
+    } else {
+        symlink_metadata(path)
+    }
+}
+
+// the name of a file in the long view, followed by where it points to if it's
+// a link. the target is colored by its own type, or as missing and followed by
+// a notice if it's broken
+fn get_long_name(path: &str, name: &str, md: &Metadata, colors: &Colors) -> String {
+    let name = colors.paint_name(path, name);
+    if !md.file_type().is_symlink() {
+        return name;
+    }
+
+    let target = match read_link(path) {
+        Ok(t) => t,
+        Err(_) => return name,
+    };
+    let target_str = target.to_string_lossy();
+
+    // relative targets are relative to the directory the link is in
+    let target_path = Path::new(path).parent().unwrap_or(Path::new("")).join(&target);
+    let target_str = if metadata(&target_path).is_ok() {
+        colors.paint_name(&target_path.to_string_lossy(), &target_str)
+    } else {
+        format!("{} {}", colors.paint_type("mi", &target_str), BROKEN_LINK_NOTICE)
+    };
+
+    format!("{} {} {}", name, LINK_ARROW, target_str)
+}
+
+/// Identifies a file by its device and inode numbers.
+pub fn file_id(md: &Metadata) -> (u64, u64) {
+    (md.dev(), md.ino())
+}
+
+/// Reads and sorts the entries of a directory, skipping the ones that can't be read.
+pub fn read_files(path: &str, out: &mut Output, args: &Args) -> Result<Vec<DirEntry>, Error> {
+    let mut files = Vec::new();
+    for r in read_dir(path).map_err(|e| Error::path(path, e))? {
+        match r {
+            Ok(file) => files.push(file),
+            Err(e) => error::report(&Error::path(path, e), error::EXIT_MINOR, out),
+        }
     }
+
+    sort::sort_files(&mut files, args);
+
+    Ok(files)
 }
 
-fn list_in_grid(items: Vec<String>, margin: usize, args: &Args) {
+fn list_in_grid(items: Vec<String>, margin: usize, out: &mut Output, args: &Args)
+    -> Result<(), Error> {
     let direction = if args.across {
         Direction::LeftToRight
     } else {
@@ -112,77 +477,152 @@ fn list_in_grid(items: Vec<String>, margin: usize, args: &Args) {
 
     for item in &items {
         grid.add(Cell {
-            width: item.len(),
+            width: color::display_width(item),
             contents: item.to_string(),
             alignment: Alignment::Left,
         });
     }
 
-    let term_width = match term_size::dimensions() {
-        Some((w, _)) => w,
-        None => panic!("Couldn't determine terminal width."),
+    // like ls, piped output has one item per line unless a grid is asked for
+    let term_width = match output::get_width(args) {
+        Some(w) if args.grid || io::stdout().is_terminal() => w,
+        None if args.grid => DEFAULT_WIDTH,
+        _ => return list_one_per_line(items, out),
     };
 
     if let Some(display) = grid.fit_into_width(term_width) {
-        print!("{}", display);
+        write!(out, "{}", display)?;
+        Ok(())
     } else {
-        list_one_per_line(items);
+        list_one_per_line(items, out)
     }
 }
 
-fn list_one_per_line(items: Vec<String>) {
+fn list_one_per_line(items: Vec<String>, out: &mut Output) -> Result<(), Error> {
     for item in items {
-        println!("{}", item);
+        writeln!(out, "{}", item)?;
     }
+
+    Ok(())
 }
 
-fn get_file_paths(files: &Vec<DirEntry>, base: &str, args: &Args) -> Vec<String> {
+/// The paths of the entries that are listed, leaving out hidden, ignored and
+/// filtered out ones.
+pub fn get_file_paths(files: &Vec<DirEntry>, base: &str, filter: &Filter, args: &Args)
+    -> Vec<String> {
     let mut paths = Vec::with_capacity(files.len());
     for file in files {
-        let path = file.file_name().into_string().unwrap();
+        let name = file.file_name().to_string_lossy().into_owned();
+        let path = format!("{}/{}", base, name);
+
+        // hidden and ignored files
+        if filter.is_ignored(&path, &name, is_real_dir(file)) {
+            continue;
+        }
 
-        // check if hidden file
-        if &path[0..1] == "." && !args.all {
+        // trees keep directories with --only-files since the files are in them
+        let is_dir = sort::is_dir(file, args);
+        if (args.only_dirs && !is_dir) || (args.only_files && is_dir && !args.tree) {
             continue;
         }
 
-        paths.push(format!("{}/{}", base, file.file_name().into_string().unwrap()));
+        paths.push(path);
     }
     paths
 }
 
-fn get_short_form_items(paths: &Vec<String>) -> Vec<String> {
+/// Whether an entry is a directory itself rather than a link to one, which is
+/// what ignore rules go by.
+pub fn is_real_dir(file: &DirEntry) -> bool {
+    file.file_type().is_ok_and(|t| t.is_dir())
+}
+
+fn get_short_form_items(paths: &Vec<String>, colors: &Colors, git: Option<&GitStatus>)
+    -> Vec<String> {
     let mut items = Vec::new();
 
     for path in paths {
-        items.push(String::from(path.split('/').last().unwrap()));
+        let name = colors.paint_name(path, path.split('/').next_back().unwrap());
+
+        // a marker in front of the name shows the git status
+        items.push(match git.and_then(|g| g.get(path)) {
+            Some(status) => format!("{} {}", git::paint_marker(status, colors), name),
+            None => name,
+        });
     }
 
     items
 }
 
-fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
-    let mut items = Vec::new();
+fn get_long_form_items(paths: &Vec<String>, colors: &Colors, git: Option<&GitStatus>,
+    out: &mut Output, args: &Args)
+    -> (Option<String>, Vec<String>) {
+    // get metadata once, files that vanished or can't be read are left out
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        match get_metadata(path, args) {
+            Ok(md) => entries.push((path.clone(), md)),
+            Err(e) => error::report(&Error::path(path, e), error::EXIT_MINOR, out),
+        }
+    }
+
+    let (header, columns) = get_long_columns(&entries, colors, git, args);
+
+    let items = entries.iter()
+        .zip(columns)
+        .map(|((path, md), c)| {
+            let name = path.split('/').next_back().unwrap();
+            c + &get_long_name(path, name, md, colors)
+        })
+        .collect();
+
+    (header.map(|h| h + &colors.paint("hd", NAME_LABEL)), items)
+}
+
+// builds the metadata columns of the long view, padded to line up and ending
+// in a space so the name can follow. with --header the row of column titles
+// comes first, also without the name
+fn get_long_columns(entries: &[(String, Metadata)], colors: &Colors, git: Option<&GitStatus>,
+    args: &Args) -> (Option<String>, Vec<String>) {
+    let mut items = Vec::with_capacity(entries.len());
 
     // get widths for some columns
     let mut widths = HashMap::new();
 
-    // also cache file sizes and times
-    let mut sizes = HashMap::with_capacity(paths.len());
-    let mut modifieds = HashMap::with_capacity(paths.len());
-    let mut changeds = HashMap::with_capacity(paths.len());
-    let mut createds = HashMap::with_capacity(paths.len());
-    let mut accesseds = HashMap::with_capacity(paths.len());
-    for path in paths {
-        let md = match metadata(path) {
-            Ok(x) => x,
-            Err(e) => panic!("Failed to retrieve metadata: {}", e),
-        };
+    // names are looked up once per user and group
+    let users = UsersCache::new();
+    let current_uid = users.get_current_uid();
+    let current_gids: Vec<u32> = users.get_user_by_uid(current_uid)
+        .and_then(|u| u.groups())
+        .map(|groups| groups.iter().map(|g| g.gid()).collect())
+        .unwrap_or_else(|| vec![users.get_current_gid()]);
+
+    // also cache file sizes, times and extended attribute markers
+    let mut markers = HashMap::new();
+    let mut sizes = HashMap::with_capacity(entries.len());
+    let mut modifieds = HashMap::with_capacity(entries.len());
+    let mut changeds = HashMap::with_capacity(entries.len());
+    let mut createds = HashMap::with_capacity(entries.len());
+    let mut accesseds = HashMap::with_capacity(entries.len());
+
+    for (path, md) in entries {
 
         if args.inode {
             update_width(&mut widths, "inode", md.ino().to_string().len());
         }
 
+        if !args.no_permissions {
+            update_width(&mut widths, "permissions", PERMISSIONS_WIDTH);
+
+            if let Some(marker) = get_attribute_marker(path) {
+                markers.insert(path, marker);
+            }
+        }
+
+        if args.octal_permissions {
+            update_width(&mut widths, "octal", OCTAL_WIDTH);
+        }
+
         if args.links {
             update_width(&mut widths, "links", md.nlink().to_string().len());
         }
@@ -190,6 +630,8 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
         // file sizes get cached because they need more processing here anyway
         if !args.no_filesize {
             let mut size_str;
+            // sizes are colored by their scale
+            let mut size_key = SIZE_KEYS[0];
 
             if md.is_file() {
                 let size = md.size();
@@ -199,12 +641,11 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
                 } else {
                     let mut size = size as f64;
 
-                    let magnitude;
-                    if args.binary {
-                        magnitude = 1024.0;
+                    let magnitude = if args.binary {
+                        1024.0
                     } else {
-                        magnitude = 1000.0;
-                    }
+                        1000.0
+                    };
 
                     // set order of magnitude
                     let mut order = 0;
@@ -214,6 +655,8 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
                     }
 
 
+                    size_key = SIZE_KEYS[order.min(SIZE_KEYS.len() - 1)];
+
                     if order > 0 {
                         size_str = format_size(size);
 
@@ -227,11 +670,12 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
                 }
             } else {
                 size_str = DIRECTORY_SIZE.to_string();
+                size_key = NO_VALUE_KEY;
             }
 
             update_width(&mut widths, "size", size_str.len());
 
-            sizes.insert(path, size_str);
+            sizes.insert(path, (size_str, size_key));
         }
 
         if args.blocks {
@@ -239,20 +683,16 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
         }
 
         if !args.no_user {
-            update_width(&mut widths, "user", md.uid().to_string().len());
+            update_width(&mut widths, "user", get_user_name(md.uid(), &users, args).len());
         }
 
         if args.group {
-            update_width(&mut widths, "group", md.gid().to_string().len());
+            update_width(&mut widths, "group", get_group_name(md.gid(), &users, args).len());
         }
 
         if !args.no_time {
             if args.modified {
-                let dt: DateTime<Local> = match md.modified() {
-                    Ok(st) => st.into(),
-                    Err(e) => panic!("Failed to retrieve file timestamp: {}", e),
-                };
-                let time_str = dt.format(TIME_FORMAT).to_string();
+                let time_str = time::format_time(md.modified(), &args.time_style);
 
                 update_width(&mut widths, "modified", time_str.len());
 
@@ -260,9 +700,7 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
             }
             
             if args.changed {
-                let st = SystemTime::UNIX_EPOCH + Duration::from_secs(md.ctime() as u64);
-                let dt: DateTime<Local> = st.into();
-                let time_str = dt.format(TIME_FORMAT).to_string();
+                let time_str = time::format_time(time::get_changed(md), &args.time_style);
 
                 update_width(&mut widths, "changed", time_str.len());
 
@@ -270,11 +708,7 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
             }
 
             if args.created {
-                let dt: DateTime<Local> = match md.created() {
-                    Ok(st) => st.into(),
-                    Err(e) => panic!("Failed to retrieve file timestamp: {}", e),
-                };
-                let time_str = dt.format(TIME_FORMAT).to_string();
+                let time_str = time::format_time(md.created(), &args.time_style);
 
                 update_width(&mut widths, "created", time_str.len());
 
@@ -282,11 +716,7 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
             }
 
             if args.accessed {
-                let dt: DateTime<Local> = match md.accessed() {
-                    Ok(st) => st.into(),
-                    Err(e) => panic!("Failed to retrieve file timestamp: {}", e),
-                };
-                let time_str = dt.format(TIME_FORMAT).to_string();
+                let time_str = time::format_time(md.accessed(), &args.time_style);
 
                 update_width(&mut widths, "accessed", time_str.len());
 
@@ -295,49 +725,77 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
         }
     }
 
-    for path in paths {
-        let md = match metadata(path) {
-            Ok(x) => x,
-            Err(e) => panic!("Failed to retrieve metadata: {}", e),
-        };
+    if git.is_some() {
+        update_width(&mut widths, "git", GIT_STATUS_PLAIN.len());
+    }
+
+    // the marker gets its own character after the permissions
+    if !markers.is_empty() {
+        update_width(&mut widths, "permissions", PERMISSIONS_WIDTH + 1);
+    }
+
+    // the titles take part in the widths so that they line up with the columns
+    let header = if args.header {
+        let labels = get_header_labels(git.is_some(), args);
+        for (key, label) in &labels {
+            update_width(&mut widths, key, label.len());
+        }
+
+        let mut header = String::new();
+        for (key, label) in &labels {
+            let width = *widths.get(*key).unwrap();
+
+            // permissions are the only column aligned to the left
+            if *key == "permissions" {
+                header.push_str(&colors.paint("hd", label));
+                header.push_str(&" ".repeat(width - label.len() + 1));
+            } else {
+                push_pad_str(&mut header, label, width, colors.paint("hd", label));
+            }
+        }
+
+        Some(header)
+    } else {
+        None
+    };
+
+    for (path, md) in entries {
         let mut item_str = String::new();
 
         if args.inode {
             push_pad_str(&mut item_str, &md.ino().to_string(),
-                *widths.get("inode").unwrap());
+                *widths.get("inode").unwrap(), colors.paint("in", &md.ino().to_string()));
         }
 
-        if !args.no_permissions {
-            item_str.push(if md.is_dir() {
-                'd'
-            } else {
-                '.'
-            });
-
-            // get file permissions as a bit slice
-            let bit_arr = md.permissions().mode().into_bitarray::<Msb0>();
-            let (_, perms) = bit_arr.split_at(32 - 9);
+        if args.octal_permissions {
+            let octal = format!("{:04o}", md.permissions().mode() & PERMISSION_BITS);
+            push_pad_str(&mut item_str, &octal, *widths.get("octal").unwrap(),
+                colors.paint("oc", &octal));
+        }
 
-            // iterate over bit slice and permission letters
-            for i in 0..perms.len() {
-                item_str.push(if perms[i] {
-                    PERM_CHARS[i % 3]
-                } else {
-                    NO_PERM
-                });
+        if !args.no_permissions {
+            item_str.push_str(&get_permissions(md, colors));
+
+            // files without a marker get a space so the columns still line up
+            let mut width = PERMISSIONS_WIDTH;
+            if !markers.is_empty() {
+                let marker = markers.get(path).unwrap_or(&' ').to_string();
+                item_str.push_str(&colors.paint("xa", &marker));
+                width += 1;
             }
 
-            item_str.push(' ');
+            item_str.push_str(&" ".repeat(widths.get("permissions").unwrap() - width + 1));
         }
 
         if args.links {
             push_pad_str(&mut item_str, &md.nlink().to_string(),
-                *widths.get("links").unwrap());
+                *widths.get("links").unwrap(), colors.paint("lc", &md.nlink().to_string()));
         }
 
         if !args.no_filesize {
-            push_pad_str(&mut item_str, &sizes.get(&path).unwrap(),
-                *widths.get("size").unwrap());
+            let (size_str, size_key) = sizes.get(path).unwrap();
+            push_pad_str(&mut item_str, size_str, *widths.get("size").unwrap(),
+                colors.paint(size_key, size_str));
         }
 
         if args.blocks {
@@ -346,49 +804,214 @@ fn get_long_form_items(paths: &Vec<String>, args: &Args) -> Vec<String> {
                 x => x.to_string(),
             };
 
-            push_pad_str(&mut item_str, &blocks_str, *widths.get("blocks").unwrap());
+            push_pad_str(&mut item_str, &blocks_str, *widths.get("blocks").unwrap(),
+                colors.paint("bl", &blocks_str));
         }
 
         if !args.no_user {
-            push_pad_str(&mut item_str, &md.uid().to_string(),
-                *widths.get("user").unwrap());
+            // the files of the current user and their groups stand out
+            let user = get_user_name(md.uid(), &users, args);
+            let key = if md.uid() == current_uid {
+                "uu"
+            } else {
+                "un"
+            };
+            push_pad_str(&mut item_str, &user, *widths.get("user").unwrap(),
+                colors.paint(key, &user));
         }
 
         if args.group {
-            push_pad_str(&mut item_str, &md.gid().to_string(),
-                *widths.get("group").unwrap());
+            let group = get_group_name(md.gid(), &users, args);
+            let key = if current_gids.contains(&md.gid()) {
+                "gu"
+            } else {
+                "gn"
+            };
+            push_pad_str(&mut item_str, &group, *widths.get("group").unwrap(),
+                colors.paint(key, &group));
         }
 
         if !args.no_time {
             if args.modified {
-                push_pad_str(&mut item_str, &modifieds.get(&path).unwrap(),
-                    *widths.get("modified").unwrap());
+                let time_str = modifieds.get(path).unwrap();
+                push_pad_str(&mut item_str, time_str, *widths.get("modified").unwrap(),
+                    colors.paint("da", time_str));
             }
 
             if args.changed {
-                push_pad_str(&mut item_str, &changeds.get(&path).unwrap(),
-                    *widths.get("changed").unwrap());
+                let time_str = changeds.get(path).unwrap();
+                push_pad_str(&mut item_str, time_str, *widths.get("changed").unwrap(),
+                    colors.paint("da", time_str));
             }
 
             if args.created {
-                push_pad_str(&mut item_str, &createds.get(&path).unwrap(),
-                    *widths.get("created").unwrap());
+                let time_str = createds.get(path).unwrap();
+                push_pad_str(&mut item_str, time_str, *widths.get("created").unwrap(),
+                    colors.paint("da", time_str));
             }
 
             if args.accessed {
-                push_pad_str(&mut item_str, &accesseds.get(&path).unwrap(),
-                    *widths.get("accessed").unwrap());
+                let time_str = accesseds.get(path).unwrap();
+                push_pad_str(&mut item_str, time_str, *widths.get("accessed").unwrap(),
+                    colors.paint("da", time_str));
             }
         }
 
-        items.push(item_str + path.split('/').last().unwrap());
+        if let Some(git) = git {
+            // files outside the repository show as unchanged
+            let status = git.get(path).unwrap_or((NO_GIT_STATUS, NO_GIT_STATUS));
+            push_pad_str(&mut item_str, GIT_STATUS_PLAIN, *widths.get("git").unwrap(),
+                git::paint_status(status, colors));
+        }
+
+        items.push(item_str);
     }
 
-    items
+    (header, items)
+}
+
+/// The titles of the long view's columns that are shown, by their keys in the
+/// widths, without the name.
+pub fn get_header_labels(has_git: bool, args: &Args) -> Vec<(&'static str, &'static str)> {
+    let mut labels = Vec::new();
+
+    if args.inode {
+        labels.push(("inode", "Inode"));
+    }
+    if args.octal_permissions {
+        labels.push(("octal", "Octal"));
+    }
+    if !args.no_permissions {
+        labels.push(("permissions", "Permissions"));
+    }
+    if args.links {
+        labels.push(("links", "Links"));
+    }
+    if !args.no_filesize {
+        labels.push(("size", "Size"));
+    }
+    if args.blocks {
+        labels.push(("blocks", "Blocks"));
+    }
+    if !args.no_user {
+        labels.push(("user", "User"));
+    }
+    if args.group {
+        labels.push(("group", "Group"));
+    }
+    if !args.no_time {
+        if args.modified {
+            labels.push(("modified", "Date Modified"));
+        }
+        if args.changed {
+            labels.push(("changed", "Date Changed"));
+        }
+        if args.created {
+            labels.push(("created", "Date Created"));
+        }
+        if args.accessed {
+            labels.push(("accessed", "Date Accessed"));
+        }
+    }
+    if has_git {
+        labels.push(("git", "Git"));
+    }
+
+    labels
+}
+
+/// The type character and the rwx of the user, group and others, with the
+/// special bits in place of the x like ls. Colored one character at a time.
+pub fn get_permissions(md: &Metadata, colors: &Colors) -> String {
+    let mut perm_str = get_type_char(md, colors);
+
+    let mode = md.permissions().mode();
+
+    // get file permissions as a bit slice
+    let bit_arr = mode.into_bitarray::<Msb0>();
+    let (_, perms) = bit_arr.split_at(32 - 9);
+
+    // iterate over bit slice and permission letters
+    for (i, perm) in perms.iter().enumerate() {
+        let class = i / 3;
+        let is_special = i % 3 == 2 && mode & SPECIAL_BITS[class] != 0;
+
+        // a special bit without the x under it is shown in upper case
+        perm_str.push_str(&if is_special {
+            let c = SPECIAL_CHARS[class];
+            let c = if *perm {
+                c
+            } else {
+                c.to_ascii_uppercase()
+            };
+            colors.paint("su", &c.to_string())
+        } else if *perm {
+            let key = format!("{}{}", PERM_CLASSES[class], PERM_CHARS[i % 3]);
+            colors.paint(&key, &PERM_CHARS[i % 3].to_string())
+        } else {
+            colors.paint(NO_VALUE_KEY, &NO_PERM.to_string())
+        });
+    }
+
+    perm_str
+}
+
+// the first character of the permissions, colored like the name of that type
+fn get_type_char(md: &Metadata, colors: &Colors) -> String {
+    let file_type = md.file_type();
+
+    let (key, c) = if file_type.is_dir() {
+        ("di", "d")
+    } else if file_type.is_symlink() {
+        ("ln", "l")
+    } else if file_type.is_socket() {
+        ("so", "s")
+    } else if file_type.is_fifo() {
+        ("pi", "|")
+    } else if file_type.is_block_device() {
+        ("bd", "b")
+    } else if file_type.is_char_device() {
+        ("cd", "c")
+    } else {
+        return String::from(".");
+    };
+
+    colors.paint_type(key, c)
+}
+
+// a marker for files with ACLs or other extended attributes
+fn get_attribute_marker(path: &str) -> Option<char> {
+    let mut attributes = xattr::list(path).ok()?.peekable();
+    attributes.peek()?;
+
+    if attributes.any(|a| a == ACL_ATTRIBUTE) {
+        Some(ACL_MARKER)
+    } else {
+        Some(XATTR_MARKER)
+    }
+}
+
+/// The name of a user, or their uid with `--numeric` or if they have no name.
+pub fn get_user_name(uid: u32, users: &UsersCache, args: &Args) -> String {
+    match users.get_user_by_uid(uid) {
+        Some(user) if !args.numeric => user.name().to_string_lossy().into_owned(),
+        _ => uid.to_string(),
+    }
+}
+
+/// The name of a group, or its gid with `--numeric` or if it has no name.
+pub fn get_group_name(gid: u32, users: &UsersCache, args: &Args) -> String {
+    match users.get_group_by_gid(gid) {
+        Some(group) if !args.numeric => group.name().to_string_lossy().into_owned(),
+        _ => gid.to_string(),
+    }
 }
 
-fn push_pad_str(a: &mut String, b: &str, pad: usize) {
-    a.push_str(&format!("{:>pad$} ", b));
+// pads by the width of the plain text since the colored one has escapes in it
+fn push_pad_str(a: &mut String, b: &str, pad: usize, colored: String) {
+    a.push_str(&" ".repeat(pad.saturating_sub(b.len())));
+    a.push_str(&colored);
+    a.push(' ');
 }
 
 // formats integer with thousands separator
@@ -1,12 +1,30 @@
 use clap::{
     builder::{ NonEmptyStringValueParser, PossibleValuesParser, TypedValueParser },
-    Parser,
+    ArgAction, Parser,
 };
-use std::ops::Range;
-
+use std::{ io, process };
+
+use error::Error;
+use globset::GlobSet;
+use print::LineRange;
+use time::TimeStyle;
+
+pub mod binary;
+pub mod color;
+pub mod error;
+pub mod export;
+pub mod filter;
+pub mod git;
+pub mod highlight;
+pub mod json;
 pub mod list;
+pub mod nonprintable;
+pub mod output;
 pub mod print;
 pub mod sort;
+pub mod time;
+pub mod tree;
+pub mod wrap;
 
 mod path;
 
@@ -18,16 +36,31 @@ fn main() {
         args.modified = true;
     }
 
-    if args.list_dirs && !args.recurse {
-        list::list_dirs(&args);
+    let mut out = output::Output::new(&args);
+
+    let result = if args.json || args.ndjson {
+        json::list_json(&mut out, &args)
+    } else if args.format.is_some() {
+        export::list_table(&mut out, &args)
+    } else if args.list_dirs && !args.recurse {
+        list::list_dirs(&mut out, &args)
     } else {
         // decide what to do depending on the number of paths
         match args.paths.len() {
-            0 => path::handle_path(-1, &args),  // list current directory
-            1 => path::handle_path(0, &args),   // list or print
-            _ => path::handle_paths(&args),                 // list or print each path
+            0 => path::handle_path(-1, &mut out, &args),  // list current directory
+            1 => path::handle_path(0, &mut out, &args),   // list or print
+            _ => path::handle_paths(&mut out, &args),     // list or print each path
         }
+    };
+
+    // the pager or the reading end of a pipe closing early isn't an error
+    match result.and_then(|_| out.finish().map_err(Error::Output)) {
+        Err(Error::Output(e)) if e.kind() == io::ErrorKind::BrokenPipe => {},
+        Err(e) => error::report(&e, error::EXIT_SERIOUS, &mut out),
+        Ok(()) => {},
     }
+
+    process::exit(error::exit_code());
 }
 
 // help message headings
@@ -48,6 +81,10 @@ pub struct Args {
 
     // File print options
     
+    /// Show the file as a hexdump. Binary files are otherwise only described
+    #[arg(long, help_heading = FILE_PRINT_HEADING)]
+    hexdump: bool,
+
     /// Show non-printable characters
     #[arg(short = 'A', long, help_heading = FILE_PRINT_HEADING)]
     show_all: bool,
@@ -57,7 +94,12 @@ pub struct Args {
         help_heading = FILE_PRINT_HEADING)]
     file_name: String,
 
-    /// Show line numbers
+    /// Set the language for syntax highlighting
+    #[arg(long, default_value = "", value_name = "LANG", hide_default_value = true,
+        help_heading = FILE_PRINT_HEADING)]
+    language: String,
+
+    /// Show line numbers, even when the output isn't a terminal
     #[arg(short = 'N', long, help_heading = FILE_PRINT_HEADING)]
     numbers: bool,
 
@@ -67,14 +109,30 @@ pub struct Args {
         help_heading = FILE_PRINT_HEADING)]
     paging: String,
 
-    /// Only print the lines from N to M
-    #[arg(short = 'r', long, value_parser = RangeValueParser, default_value = "-1:-1",
-        value_name = "N:M", hide_default_value = true, help_heading = FILE_PRINT_HEADING)]
-    line_range: Range<isize>,
+    /// Only print the lines from N to M. Either end can be left out and negative numbers count
+    /// from the end of the file, e.g. -30: prints the last 30 lines. Can be given multiple times.
+    /// Ranges are in bytes for hexdumps
+    #[arg(short = 'r', long, value_parser = RangeValueParser, action = ArgAction::Append,
+        value_name = "N:M", help_heading = FILE_PRINT_HEADING)]
+    line_range: Vec<LineRange>,
+
+    /// Comma-separated list of decorations to show around the file. Auto is full for terminals
+    /// and plain otherwise
+    #[arg(long, value_delimiter = ',',
+        value_parser = PossibleValuesParser::new(["auto", "full", "plain", "changes", "grid",
+            "header", "numbers", "snip"]),
+        default_value = "auto", value_name = "COMPONENTS", hide_default_value = true,
+        help_heading = FILE_PRINT_HEADING)]
+    style: Vec<String>,
+
+    /// Set the color theme for syntax highlighting
+    #[arg(long, default_value = "", value_name = "THEME", hide_default_value = true,
+        help_heading = FILE_PRINT_HEADING)]
+    theme: String,
 
-    /// Specify text wrapping mode
+    /// Specify text wrapping mode. Never cuts long lines short
     #[arg(short, short_alias = 'W', long,
-        value_parser = PossibleValuesParser::new(["auto", "never", "character"]),
+        value_parser = PossibleValuesParser::new(["auto", "never", "character", "word"]),
         default_value = "auto", value_name = "MODE", hide_default_value = true,
         help_heading = FILE_PRINT_HEADING)]
     wrap: String,
@@ -82,6 +140,13 @@ pub struct Args {
 
     // Dir list formatting options
     
+    /// Specify when to use colors. Auto colors terminals unless NO_COLOR is set
+    #[arg(long, visible_alias = "colour",
+        value_parser = PossibleValuesParser::new(["auto", "always", "never"]),
+        default_value = "auto", value_name = "WHEN", hide_default_value = true,
+        help_heading = DIR_LIST_FORMAT_HEADING)]
+    color: String,
+
     /// Display one item per line
     #[arg(short = '1', long, help_heading = DIR_LIST_FORMAT_HEADING)]
     oneline: bool,
@@ -98,10 +163,30 @@ pub struct Args {
     #[arg(short = 'R', long, help_heading = DIR_LIST_FORMAT_HEADING)]
     recurse: bool,
 
+    /// List entries as a JSON array with every field of the long view as raw values. Trees nest
+    /// the contents of each directory in its children
+    #[arg(long, help_heading = DIR_LIST_FORMAT_HEADING)]
+    json: bool,
+
+    /// List entries as JSON with one entry per line, trees included
+    #[arg(long, overrides_with = "json", help_heading = DIR_LIST_FORMAT_HEADING)]
+    ndjson: bool,
+
+    /// List entries as comma or tab separated values with the columns of the long view and a
+    /// header row. Sizes are in bytes and times in seconds since the epoch, or ISO 8601 with a
+    /// time style other than the default
+    #[arg(long, value_parser = PossibleValuesParser::new(["csv", "tsv"]), value_name = "FORMAT",
+        help_heading = DIR_LIST_FORMAT_HEADING)]
+    format: Option<String>,
+
     /// Recurse into directories as a tree
     #[arg(short = 'T', long, overrides_with = "grid", help_heading = DIR_LIST_FORMAT_HEADING)]
     tree: bool,
 
+    /// Set the width to fit grids and wrapped lines into instead of the terminal width
+    #[arg(long, value_name = "COLS", help_heading = DIR_LIST_FORMAT_HEADING)]
+    width: Option<usize>,
+
     /// Sort the grid across
     #[arg(short = 'x', short_alias = 'X', long, help_heading = DIR_LIST_FORMAT_HEADING)]
     across: bool,
@@ -121,15 +206,45 @@ pub struct Args {
     #[arg(short = 'D', long, help_heading = DIR_LIST_FILT_SORT_HEADING)]
     only_dirs: bool,
 
+    /// List files only; don't list directories
+    #[arg(short = 'f', long, overrides_with = "only_dirs", help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    only_files: bool,
+
     /// Set the level of recursion
     #[arg(short = 'L', long, default_value = "-1", value_name = "DEPTH", hide_default_value = true,
         help_heading = DIR_LIST_FILT_SORT_HEADING)]
     level: isize,
 
+    /// Leave out files ignored by git, through .gitignore, .git/info/exclude or the global
+    /// excludes file
+    #[arg(long, help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    git_ignore: bool,
+
+    /// Leave out files whose names match any of the globs, separated by '|'
+    #[arg(long, value_parser = GlobValueParser, value_name = "GLOBS",
+        help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    ignore_glob: Option<GlobSet>,
+
     /// List all directories before files
     #[arg(short = 'q', short_alias = 'Q', long, help_heading = DIR_LIST_FILT_SORT_HEADING)]
     group_directories_first: bool,
 
+    /// Reverse the sort order
+    #[arg(long, help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    reverse: bool,
+
+    /// Sort by a field. Version is the same as natural, which orders numbers in names by value
+    #[arg(short, long,
+        value_parser = PossibleValuesParser::new(["name", "natural", "version", "extension",
+            "size", "modified", "changed", "accessed", "created", "inode", "type", "none"]),
+        default_value = "name", value_name = "FIELD", hide_default_value = true,
+        help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    sort: String,
+
+    /// Count symlinks to directories as directories when grouping and filtering
+    #[arg(long, help_heading = DIR_LIST_FILT_SORT_HEADING)]
+    symlink_dirs: bool,
+
 
     // Dir long listing field options
 
@@ -145,10 +260,19 @@ pub struct Args {
     #[arg(short, short_alias = 'C', long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     changed: bool,
 
+    /// List each file's git status, staged and unstaged. Directories show the status of their
+    /// contents and grids get a marker before each name
+    #[arg(long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
+    git: bool,
+
     /// List each file's group
     #[arg(short, long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     group: bool,
 
+    /// Follow symlinks and list what they point to instead
+    #[arg(long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
+    dereference: bool,
+
     /// Show a header for each column
     #[arg(short = 'H', long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     header: bool,
@@ -169,6 +293,10 @@ pub struct Args {
     #[arg(short, long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     numeric: bool,
 
+    /// List each file's permission bits in octal
+    #[arg(long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
+    octal_permissions: bool,
+
     /// List each file's number of file system blocks
     #[arg(short = 'S', long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     blocks: bool,
@@ -181,6 +309,13 @@ pub struct Args {
     #[arg(short = 'U', long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     created: bool,
 
+    /// Set how timestamps are shown: default, iso, long-iso, full-iso, relative or +FORMAT for
+    /// a strftime format. The default and iso show the year instead of the time for files older
+    /// than six months
+    #[arg(long, value_parser = TimeStyleValueParser, default_value = "default",
+        value_name = "STYLE", hide_default_value = true, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
+    time_style: TimeStyle,
+
     /// Hide the permissions field
     #[arg(short = 'o', short_alias = 'O', long, help_heading = DIR_LIST_LONG_VIEW_HEADING)]
     no_permissions: bool,
@@ -202,26 +337,130 @@ pub struct Args {
 struct RangeValueParser;
 
 impl TypedValueParser for RangeValueParser {
-    type Value = Range<isize>;
+    type Value = LineRange;
 
     fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &std::ffi::OsStr)
         -> Result<Self::Value, clap::Error> {
             let val_str = NonEmptyStringValueParser::new().parse_ref(cmd, arg, value)?;
             let vals: Vec<&str> = val_str.split(':').collect();
 
-            if vals.len() != 2 {
+            // a single number is a range of one line
+            let (start, end) = match vals.len() {
+                1 => (parse_line_num(vals[0])?, parse_line_num(vals[0])?),
+                2 => (parse_line_num(vals[0])?, parse_line_num(vals[1])?),
+                _ => return Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
+            };
+
+            if start.is_none() && end.is_none() {
                 return Err(clap::Error::new(clap::error::ErrorKind::InvalidValue));
             }
 
-            let start: isize = match vals[0].parse() {
-                Ok(x) => x,
-                Err(_) => return Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
-            };
-            let end: isize = match vals[1].parse() {
-                Ok(x) => x,
-                Err(_) => return Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
-            };
+            // both ends counting from the same side have to be in order
+            if let (Some(s), Some(e)) = (start, end) {
+                if s.signum() == e.signum() && s > e {
+                    return Err(clap::Error::new(clap::error::ErrorKind::InvalidValue));
+                }
+            }
+
+            Ok(LineRange { start, end })
+        }
+}
+
+#[derive(Clone)]
+struct TimeStyleValueParser;
+
+impl TypedValueParser for TimeStyleValueParser {
+    type Value = TimeStyle;
 
-            Ok(start..end)
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &std::ffi::OsStr)
+        -> Result<Self::Value, clap::Error> {
+            let val_str = NonEmptyStringValueParser::new().parse_ref(cmd, arg, value)?;
+
+            match TimeStyle::parse(&val_str) {
+                Some(style) => Ok(style),
+                None => Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
+            }
+        }
+}
+
+#[derive(Clone)]
+struct GlobValueParser;
+
+impl TypedValueParser for GlobValueParser {
+    type Value = GlobSet;
+
+    fn parse_ref(&self, cmd: &clap::Command, arg: Option<&clap::Arg>, value: &std::ffi::OsStr)
+        -> Result<Self::Value, clap::Error> {
+            let val_str = NonEmptyStringValueParser::new().parse_ref(cmd, arg, value)?;
+
+            match filter::parse_globs(&val_str) {
+                Some(globs) => Ok(globs),
+                None => Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
+            }
         }
 }
+
+// parses one end of a line range, an empty string leaves that end open
+fn parse_line_num(val: &str) -> Result<Option<isize>, clap::Error> {
+    if val.is_empty() {
+        return Ok(None);
+    }
+
+    match val.parse() {
+        Ok(0) | Err(_) => Err(clap::Error::new(clap::error::ErrorKind::InvalidValue)),
+        Ok(x) => Ok(Some(x)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_range(range: &str) -> Result<LineRange, clap::Error> {
+        let arg = format!("--line-range={}", range);
+        Args::try_parse_from(["eb", &arg]).map(|mut args| args.line_range.remove(0))
+    }
+
+    #[test]
+    fn line_range_open_start() {
+        let range = parse_range(":50").unwrap();
+        assert_eq!((range.start, range.end), (None, Some(50)));
+    }
+
+    #[test]
+    fn line_range_open_end() {
+        let range = parse_range("100:").unwrap();
+        assert_eq!((range.start, range.end), (Some(100), None));
+    }
+
+    #[test]
+    fn line_range_from_end() {
+        let range = parse_range("-30:").unwrap();
+        assert_eq!((range.start, range.end), (Some(-30), None));
+    }
+
+    #[test]
+    fn line_range_single_line() {
+        let range = parse_range("7").unwrap();
+        assert_eq!((range.start, range.end), (Some(7), Some(7)));
+    }
+
+    #[test]
+    fn line_range_rejects_zero() {
+        assert!(parse_range("0").is_err());
+        assert!(parse_range("0:5").is_err());
+        assert!(parse_range("5:0").is_err());
+    }
+
+    #[test]
+    fn line_range_rejects_reversed() {
+        assert!(parse_range("5:2").is_err());
+        assert!(parse_range("-2:-5").is_err());
+    }
+
+    #[test]
+    fn line_range_rejects_empty() {
+        assert!(parse_range(":").is_err());
+        assert!(parse_range("1:2:3").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use syntect::highlighting::Style;
+use unicode_width::UnicodeWidthChar;
+
+use crate::print::TAB_LENGTH;
+
+const TAB_START: char = '├';
+const TAB_MIDDLE: char = '─';
+const TAB_END: char = '┤';
+const SHORT_TAB: char = '→';
+const LF_SYMBOL: &str = "␊";
+const CRLF_SYMBOL: &str = "␍␊";
+const DEL_SYMBOL: char = '␡';
+const NBSP_SYMBOL: char = '⍽';
+const ZERO_WIDTH_SYMBOL: char = '·';
+// C0 control characters have their own symbols starting from ␀
+const CONTROL_PICTURES_START: u32 = 0x2400;
+const NBSP_CHARS: [char; 3] = ['\u{a0}', '\u{2007}', '\u{202f}'];
+const ZERO_WIDTH_CHARS: [char; 6] = ['\u{200b}', '\u{200c}', '\u{200d}', '\u{2060}', '\u{feff}',
+    '\u{ad}'];
+
+/// How a line of a file ended.
+#[derive(Clone, Copy, PartialEq)]
+pub enum LineEnding {
+    Lf,
+    Crlf,
+    None,
+}
+
+/// Replaces the tabs, control characters, unusual spaces and the line ending
+/// in the highlighted regions of a line with visible symbols. The symbols get
+/// their own style so they stand out from the text.
+pub fn show_nonprintable<S: AsRef<str>>(regions: &[(Style, S)], ending: LineEnding,
+    symbol_style: Style) -> Vec<(Style, String)> {
+    let mut result = Vec::new();
+    let mut col = 0;
+
+    for (style, text) in regions {
+        let mut part = String::new();
+
+        for c in text.as_ref().trim_end_matches('\n').chars() {
+            match get_symbol(c, col) {
+                Some(symbol) => {
+                    if !part.is_empty() {
+                        result.push((*style, std::mem::take(&mut part)));
+                    }
+                    col += symbol.chars().count();
+                    result.push((symbol_style, symbol));
+                },
+                None => {
+                    part.push(c);
+                    col += c.width().unwrap_or(0);
+                },
+            }
+        }
+
+        if !part.is_empty() {
+            result.push((*style, part));
+        }
+    }
+
+    match ending {
+        LineEnding::Lf => result.push((symbol_style, LF_SYMBOL.to_string())),
+        LineEnding::Crlf => result.push((symbol_style, CRLF_SYMBOL.to_string())),
+        LineEnding::None => {},
+    }
+
+    result
+}
+
+// the symbol for a character that would otherwise be invisible, col is the
+// column the character is in for lining tabs up with tab stops
+fn get_symbol(c: char, col: usize) -> Option<String> {
+    let symbol = match c {
+        '\t' => {
+            let width = TAB_LENGTH - col % TAB_LENGTH;
+            if width == 1 {
+                SHORT_TAB.to_string()
+            } else {
+                let mut tab = String::from(TAB_START);
+                tab.extend(std::iter::repeat_n(TAB_MIDDLE, width - 2));
+                tab.push(TAB_END);
+                tab
+            }
+        },
+        '\u{7f}' => DEL_SYMBOL.to_string(),
+        c if (c as u32) < 0x20 => {
+            char::from_u32(CONTROL_PICTURES_START + c as u32).unwrap().to_string()
+        },
+        // C1 control characters don't have symbols so show their code instead
+        c if c.is_control() => format!("<{:02X}>", c as u32),
+        c if NBSP_CHARS.contains(&c) => NBSP_SYMBOL.to_string(),
+        c if ZERO_WIDTH_CHARS.contains(&c) => ZERO_WIDTH_SYMBOL.to_string(),
+        _ => return None,
+    };
+
+    Some(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printable_characters_have_no_symbol() {
+        assert_eq!(get_symbol('a', 0), None);
+        assert_eq!(get_symbol(' ', 0), None);
+        assert_eq!(get_symbol('é', 0), None);
+        assert_eq!(get_symbol('漢', 0), None);
+    }
+
+    #[test]
+    fn tabs_reach_the_next_tab_stop() {
+        assert_eq!(get_symbol('\t', 0), Some(format!("├{}┤", "─".repeat(TAB_LENGTH - 2))));
+        assert_eq!(get_symbol('\t', TAB_LENGTH - 2), Some(String::from("├┤")));
+        assert_eq!(get_symbol('\t', TAB_LENGTH - 1), Some(String::from("→")));
+        assert_eq!(get_symbol('\t', TAB_LENGTH), get_symbol('\t', 0));
+    }
+
+    #[test]
+    fn c0_controls_use_control_pictures() {
+        assert_eq!(get_symbol('\0', 0), Some(String::from("␀")));
+        assert_eq!(get_symbol('\r', 0), Some(String::from("␍")));
+        assert_eq!(get_symbol('\x1b', 0), Some(String::from("␛")));
+        assert_eq!(get_symbol('\x7f', 0), Some(String::from("␡")));
+    }
+
+    #[test]
+    fn c1_controls_show_their_code() {
+        assert_eq!(get_symbol('\u{85}', 0), Some(String::from("<85>")));
+        assert_eq!(get_symbol('\u{9b}', 0), Some(String::from("<9B>")));
+    }
+
+    #[test]
+    fn unusual_spaces_are_marked() {
+        assert_eq!(get_symbol('\u{a0}', 0), Some(String::from("⍽")));
+        assert_eq!(get_symbol('\u{202f}', 0), Some(String::from("⍽")));
+        assert_eq!(get_symbol('\u{200b}', 0), Some(String::from("·")));
+        assert_eq!(get_symbol('\u{feff}', 0), Some(String::from("·")));
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    env,
+    io::{ self, IsTerminal, Stdout, Write },
+    mem,
+    path::Path,
+    process::{ Child, Command, Stdio },
+};
+use term_size;
+
+use crate::Args;
+
+const PAGER_VARS: [&str; 2] = ["EB_PAGER", "PAGER"];
+const DEFAULT_PAGER: &str = "less";
+const LESS_RAW_CONTROL_CHARS: &str = "-R";
+const LESS_QUIT_IF_ONE_SCREEN: &str = "-F";
+const COLUMNS_VAR: &str = "COLUMNS";
+
+#[derive(PartialEq)]
+enum Paging {
+    Auto,
+    Never,
+    Always,
+}
+
+/// Destination of everything eb prints.
+///
+/// Depending on `--paging` the output goes straight to stdout or through a
+/// pager. In auto mode output is held back until it's longer than the terminal
+/// so short listings and files never open the pager.
+pub struct Output {
+    paging: Paging,
+    height: usize,
+    buffer: Vec<u8>,
+    line_count: usize,
+    pager: Option<Child>,
+    stdout: Stdout,
+}
+
+impl Output {
+    pub fn new(args: &Args) -> Output {
+        let stdout = io::stdout();
+
+        let paging = match args.paging.as_str() {
+            "always" => Paging::Always,
+            // paging only makes sense when a person is reading the output
+            "auto" if stdout.is_terminal() => Paging::Auto,
+            _ => Paging::Never,
+        };
+
+        let height = match term_size::dimensions_stdout() {
+            Some((_, h)) => h,
+            None => usize::MAX,
+        };
+
+        let mut output = Output {
+            paging,
+            height,
+            buffer: Vec::new(),
+            line_count: 0,
+            pager: None,
+            stdout,
+        };
+
+        if output.paging == Paging::Always {
+            output.pager = spawn_pager(false);
+        }
+
+        output
+    }
+
+    /// Writes out anything held back, for when something else has to show up
+    /// after it like errors on stderr. The output isn't paged from then on.
+    pub fn release(&mut self) -> io::Result<()> {
+        if self.paging == Paging::Auto {
+            self.paging = Paging::Never;
+            let buffer = mem::take(&mut self.buffer);
+            self.stdout.write_all(&buffer)?;
+        }
+
+        self.stdout.flush()
+    }
+
+    /// Flushes any held back output and waits for the pager to be closed.
+    pub fn finish(&mut self) -> io::Result<()> {
+        let buffer = mem::take(&mut self.buffer);
+        self.stdout.write_all(&buffer)?;
+        self.stdout.flush()?;
+
+        if let Some(mut pager) = self.pager.take() {
+            // closing stdin lets the pager know there's nothing more to come
+            drop(pager.stdin.take());
+            pager.wait()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(stdin) = self.pager.as_mut().and_then(|p| p.stdin.as_mut()) {
+            return stdin.write(buf);
+        }
+
+        if self.paging != Paging::Auto {
+            return self.stdout.write(buf);
+        }
+
+        self.buffer.extend_from_slice(buf);
+        self.line_count += buf.iter().filter(|&&b| b == b'\n').count();
+
+        // the output no longer fits on the screen, so hand it over to a pager
+        if self.line_count >= self.height {
+            self.paging = Paging::Never;
+            self.pager = spawn_pager(true);
+
+            let buffer = mem::take(&mut self.buffer);
+            self.write_all(&buffer)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.pager.as_mut().and_then(|p| p.stdin.as_mut()) {
+            Some(stdin) => stdin.flush(),
+            None if self.paging == Paging::Auto => Ok(()),
+            None => self.stdout.flush(),
+        }
+    }
+}
+
+/// Width to fit the output into. `--width` comes first, then `$COLUMNS` and
+/// then the size of the terminal. Without a terminal the width is unknown.
+pub fn get_width(args: &Args) -> Option<usize> {
+    let columns = env::var(COLUMNS_VAR).ok().and_then(|c| c.parse().ok());
+
+    args.width.or(columns)
+        .filter(|&w| w > 0)
+        .or_else(|| if io::stdout().is_terminal() {
+            term_size::dimensions_stdout().map(|(w, _)| w)
+        } else {
+            None
+        })
+}
+
+// starts the pager from $EB_PAGER or $PAGER, falling back to less
+fn spawn_pager(quit_if_one_screen: bool) -> Option<Child> {
+    let pager = PAGER_VARS.iter()
+        .filter_map(|v| env::var(v).ok())
+        .find(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_PAGER.to_string());
+
+    let mut words = pager.split_whitespace();
+    let program = words.next()?;
+    let mut pager_args: Vec<&str> = words.collect();
+
+    // less needs to be told to keep colors, but only when the user hasn't
+    // configured it themselves
+    let is_less = Path::new(program).file_name().is_some_and(|n| n == "less");
+    if is_less && pager_args.is_empty() {
+        pager_args.push(LESS_RAW_CONTROL_CHARS);
+        if quit_if_one_screen {
+            pager_args.push(LESS_QUIT_IF_ONE_SCREEN);
+        }
+    }
+
+    Command::new(program)
+        .args(pager_args)
+        .stdin(Stdio::piped())
+        .spawn()
+        .ok()
+}
@@ -1,8 +1,8 @@
-use std::fs::metadata;
+use std::{ fs::metadata, io::Write };
 
-use crate::{ Args, list, print };
+use crate::{ Args, error::{ self, Error }, list, output::Output, print };
 
-pub fn handle_path(path_index: isize, args: &Args) {
+pub fn handle_path(path_index: isize, out: &mut Output, args: &Args) -> Result<(), Error> {
     // list current directory if no path was provided
     let path = if path_index < 0 {
         "."
@@ -13,23 +13,27 @@ pub fn handle_path(path_index: isize, args: &Args) {
     // get file metadata
     let md = match metadata(path) {
         Ok(x) => x,
-        Err(e) => panic!("Failed to retrieve metadata: {}", e),
+        Err(e) => return error::handle_given(Err(Error::path(path, e)), out),
     };
 
-    if md.is_dir() {
-        list::list_dir_contents(path, args);
+    let result = if md.is_dir() {
+        list::list_dir_contents(path, out, args)
     } else {
-        print::print_file(path, args);
-    }
+        print::print_file(path, out, args)
+    };
+
+    error::handle_given(result, out)
 }
 
-pub fn handle_paths(args: &Args) {
+pub fn handle_paths(out: &mut Output, args: &Args) -> Result<(), Error> {
     for i in 0..args.paths.len() {
         if i > 0 {
-            println!();
+            writeln!(out)?;
         }
 
-        println!("{}:", args.paths[i]);
-        handle_path(i as isize, args);
+        writeln!(out, "{}:", args.paths[i])?;
+        handle_path(i as isize, out, args)?;
     }
+
+    Ok(())
 }
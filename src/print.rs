@@ -1,43 +1,566 @@
 use std::{
+    collections::HashMap,
     fs::File,
-    io::{ BufRead, BufReader },
+    io::{ self, BufRead, BufReader, Cursor, IsTerminal, Read, Seek, Write },
+};
+use syntect::{
+    easy::HighlightLines,
+    highlighting::{ Color, FontStyle, Style },
+    parsing::SyntaxSet,
 };
-use term_size;
 
-use crate::Args;
+use crate::{
+    Args, binary, color,
+    error::Error,
+    git::{ self, LineChange },
+    highlight::{ self, Assets, MODELINE_LINES },
+    nonprintable::{ self, LineEnding },
+    output::{ self, Output },
+    wrap::{ self, StyledChar, WrapMode },
+};
 
-const TAB_LENGTH: usize = 8;
+pub const TAB_LENGTH: usize = 8;
+const COUNT_BUF_SIZE: usize = 64 * 1024;
+pub const SNIP_MARKER: &str = "⋮";
+const TRUNCATION_MARKER: &str = "…";
+const HEADER_LABEL: &str = "File: ";
+// how wide the grid's lines are when there's no terminal to fit them to
+const DEFAULT_BORDER_WIDTH: usize = 80;
+const GRID_HORIZONTAL: char = '─';
+const GRID_VERTICAL: char = '│';
+const GRID_TOP: char = '┬';
+const GRID_MIDDLE: char = '┼';
+const GRID_BOTTOM: char = '┴';
+// markers for lines changed since the file was staged, like bat
+const ADDED_MARKER: &str = "+";
+const MODIFIED_MARKER: &str = "~";
+const REMOVED_ABOVE_MARKER: &str = "‾";
+const REMOVED_BELOW_MARKER: &str = "_";
+const ADDED_COLOR: Color = Color { r: 0x5f, g: 0xaf, b: 0x00, a: 0xff };
+const MODIFIED_COLOR: Color = Color { r: 0xd7, g: 0xaf, b: 0x00, a: 0xff };
+const REMOVED_COLOR: Color = Color { r: 0xd7, g: 0x00, b: 0x00, a: 0xff };
+// used for the symbols of --show-all when the theme doesn't set a color
+const DEFAULT_SYMBOL_COLOR: Color = Color { r: 0x80, g: 0x80, b: 0x80, a: 0xff };
 
-pub fn print_file(path: &str, _args: &Args) {
-    let file = File::open(path).unwrap();
+/// Lines to print from a file, numbered from 1 with both ends included.
+///
+/// Negative numbers count back from the last line and a missing end reaches
+/// the start or the end of the file.
+#[derive(Clone, Debug)]
+pub struct LineRange {
+    pub start: Option<isize>,
+    pub end: Option<isize>,
+}
 
-    // get all the lines and count them
-    let mut line_count = 0;
-    let mut lines = Vec::new();
-    for line in BufReader::new(file).lines() {
-        lines.push(line.unwrap());
-        line_count += 1;
+impl LineRange {
+    // whether the file has to be read to the end to know where the range is
+    pub fn counts_from_end(&self) -> bool {
+        self.start.is_some_and(|s| s < 0) || self.end.is_none_or(|e| e < 0)
     }
 
+    // turns the range into line numbers for a file with line_count lines
+    pub fn resolve(&self, line_count: usize) -> (usize, usize) {
+        let resolve_num = |n: isize| if n < 0 {
+            (line_count as isize + n + 1).max(1) as usize
+        } else {
+            n as usize
+        };
+
+        (self.start.map_or(1, resolve_num), self.end.map_or(line_count, resolve_num))
+    }
+}
+
+pub fn print_file(path: &str, out: &mut Output, args: &Args) -> Result<(), Error> {
+    // errors from reading the file are about the path, writing ones are output
+    let read_err = |e| Error::path(path, e);
+
+    let file = File::open(path).map_err(read_err)?;
+    let md = file.metadata().map_err(read_err)?;
+    let mut file = BufReader::new(file);
+
+    // the name shown in the header
+    let name = if args.file_name.is_empty() {
+        path
+    } else {
+        &args.file_name
+    };
+
+    // look at the start of the file to tell text and binary files apart
+    let binary_type = binary::get_binary_type(file.fill_buf().map_err(read_err)?);
+
+    if args.hexdump {
+        return binary::print_hexdump(path, file, &md, out, args);
+    }
+
+    if let Some(file_type) = binary_type {
+        writeln!(out, "{}: binary file, {} bytes, type {}", name, md.len(), file_type)?;
+        return Ok(());
+    }
+
+    // when only the start of the file is wanted there's no need to read past
+    // the last line of the ranges
+    let counts_from_end = args.line_range.is_empty()
+        || args.line_range.iter().any(|r| r.counts_from_end());
+    let last_line = if counts_from_end {
+        usize::MAX
+    } else {
+        args.line_range.iter().filter_map(|r| r.end).max().unwrap() as usize
+    };
+
+    // the number of lines is needed for the line number width and for ranges
+    // counted from the end. regular files get a quick pass counting newlines,
+    // anything else can only be read once so it's kept in memory instead
+    let mut line_count = None;
+    let mut reader: Box<dyn BufRead> = if !counts_from_end {
+        Box::new(file)
+    } else if md.is_file() {
+        line_count = Some(count_lines(&mut file).map_err(read_err)?);
+        file.rewind().map_err(read_err)?;
+        Box::new(file)
+    } else {
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).map_err(read_err)?;
+        line_count = Some(count_lines(&mut contents.as_slice()).map_err(read_err)?);
+        Box::new(Cursor::new(contents))
+    };
+
+    let ranges: Vec<(usize, usize)> = match line_count {
+        Some(c) => args.line_range.iter().map(|r| r.resolve(c)).collect(),
+        None => args.line_range.iter().map(|r| r.resolve(last_line)).collect(),
+    };
+
     // count the amount to pad line numbers by
-    let line_num_length = line_count.to_string().len();
+    let line_num_length = line_count.unwrap_or(last_line).to_string().len();
+
+    // read the first few lines ahead of time to find the syntax from them
+    let mut head = Vec::new();
+    for _ in 0..MODELINE_LINES {
+        match read_line(&mut reader).map_err(read_err)? {
+            Some(line) => head.push(line),
+            None => break,
+        }
+    }
+
+    let assets = Assets::get(out);
+    let syntax = assets.get_syntax(path, &head, args)?;
+    let theme = assets.get_theme(args)?;
+
+    // only decorate, highlight and wrap when writing to a terminal unless
+    // asked for otherwise
+    let interactive = io::stdout().is_terminal();
+    let term_width = output::get_width(args);
+    let wrap_width = if args.wrap == "auto" && !interactive {
+        None
+    } else {
+        term_width
+    };
+
+    // files outside a repository get an empty column
+    let components = StyleComponents::new(args, interactive);
+    let changes = if components.changes {
+        git::get_line_changes(path).unwrap_or_default()
+    } else {
+        HashMap::new()
+    };
 
-    for i in 0..lines.len() {
-        print!("{:line_num_length$}", i + 1);
+    // highlighting is the slowest part of printing, so it's skipped when
+    // there are no colors to show
+    let colored = color::enabled(args);
+    let highlighter = if colored {
+        Some(HighlightLines::new(syntax, theme))
+    } else {
+        None
+    };
 
-        // max line length = terminal width - tab length
-        let max_line_len = match term_size::dimensions() {
-            Some((w, _)) => w - TAB_LENGTH,
-            None => panic!("Couldn't determine terminal width."),
+    let mut printer = Printer {
+        highlighter,
+        syntax_set: &assets.syntax_set,
+        ranges,
+        line_num: 0,
+        last_printed: None,
+        line_num_length,
+        wrap_width,
+        border_width: term_width.unwrap_or(DEFAULT_BORDER_WIDTH),
+        colored,
+        components,
+        changes,
+        show_all: args.show_all,
+        wrap: match args.wrap.as_str() {
+            "never" => WrapMode::Never,
+            "word" => WrapMode::Word,
+            _ => WrapMode::Character,
+        },
+        // the subtle color themes use for indent guides suits the symbols
+        symbol_style: Style {
+            foreground: theme.settings.guide
+                .or(theme.settings.gutter_foreground)
+                .unwrap_or(DEFAULT_SYMBOL_COLOR),
+            background: Color::BLACK,
+            font_style: FontStyle::empty(),
+        },
+        gutter_style: Style {
+            foreground: theme.settings.gutter_foreground.unwrap_or(DEFAULT_SYMBOL_COLOR),
+            background: Color::BLACK,
+            font_style: FontStyle::empty(),
+        },
+    };
+
+    printer.print_header(name, out)?;
+
+    for line in head {
+        printer.print_line(&line, out)?;
+    }
+
+    while printer.line_num < last_line {
+        match read_line(&mut reader).map_err(read_err)? {
+            Some(line) => printer.print_line(&line, out)?,
+            None => break,
+        }
+    }
+
+    printer.print_footer(out)?;
+    Ok(())
+}
+
+/// Decorations printed around the contents of a file, set with `--style`.
+#[derive(Clone, Copy, Default)]
+struct StyleComponents {
+    changes: bool,
+    grid: bool,
+    header: bool,
+    numbers: bool,
+    snip: bool,
+}
+
+impl StyleComponents {
+    fn new(args: &Args, interactive: bool) -> StyleComponents {
+        let full = StyleComponents {
+            changes: true,
+            grid: true,
+            header: true,
+            numbers: true,
+            snip: true,
+        };
+
+        let mut components = StyleComponents::default();
+        for component in &args.style {
+            match component.as_str() {
+                // piped output is left undecorated for other programs
+                "auto" if interactive => components = full,
+                "full" => components = full,
+                "changes" => components.changes = true,
+                "grid" => components.grid = true,
+                "header" => components.header = true,
+                "numbers" => components.numbers = true,
+                "snip" => components.snip = true,
+                "plain" => components = StyleComponents::default(),
+                _ => {},
+            }
+        }
+
+        if args.numbers {
+            components.numbers = true;
+        }
+
+        components
+    }
+}
+
+// state carried from one line of a file to the next while printing it
+struct Printer<'a> {
+    highlighter: Option<HighlightLines<'a>>,
+    syntax_set: &'a SyntaxSet,
+    ranges: Vec<(usize, usize)>,
+    line_num: usize,
+    last_printed: Option<usize>,
+    line_num_length: usize,
+    wrap_width: Option<usize>,
+    border_width: usize,
+    colored: bool,
+    components: StyleComponents,
+    changes: HashMap<usize, LineChange>,
+    show_all: bool,
+    symbol_style: Style,
+    gutter_style: Style,
+    wrap: WrapMode,
+}
+
+impl Printer<'_> {
+    fn print_line(&mut self, line: &str, out: &mut Output) -> Result<(), Error> {
+        self.line_num += 1;
+        let line_num = self.line_num;
+
+        // lines outside the ranges still go through the highlighter so that
+        // it knows the context of the lines that get printed, without one
+        // they're skipped right away
+        let in_range = self.ranges.is_empty()
+            || self.ranges.iter().any(|&(s, e)| s <= line_num && line_num <= e);
+        if !in_range && self.highlighter.is_none() {
+            return Ok(());
+        }
+
+        // the syntax set expects every line to end in a newline
+        let (text, ending) = if let Some(text) = line.strip_suffix("\r\n") {
+            (format!("{}\n", text), LineEnding::Crlf)
+        } else if line.ends_with('\n') {
+            (line.to_string(), LineEnding::Lf)
+        } else {
+            (format!("{}\n", line), LineEnding::None)
+        };
+
+        let regions = match &mut self.highlighter {
+            Some(highlighter) => match highlighter.highlight_line(&text, self.syntax_set) {
+                Ok(x) => x,
+                Err(e) => return Err(Error::Other(format!("failed to highlight line {}: {}",
+                    line_num, e))),
+            },
+            None => vec![(Style::default(), text.as_str())],
+        };
+
+        if !in_range {
+            return Ok(());
+        }
+
+        // mark the lines skipped between ranges
+        if self.components.snip && self.last_printed.is_some_and(|l| l + 1 < line_num) {
+            // without line numbers the marker goes where the text would
+            let mut line = self.gutter(SNIP_MARKER, None);
+            if !self.components.numbers {
+                push_part(&mut line, &self.gutter_style, SNIP_MARKER, self.colored);
+            }
+            writeln!(out, "{}", line)?;
+        }
+        self.last_printed = Some(line_num);
+
+        let max_line_len = match self.wrap_width {
+            Some(w) => w.saturating_sub(self.gutter_width()).max(1),
+            None => usize::MAX,
+        };
+
+        // tabs are already visible with --show-all
+        let chars = if self.show_all {
+            let regions = nonprintable::show_nonprintable(&regions, ending, self.symbol_style);
+            wrap::to_chars(&regions, false)
+        } else {
+            wrap::to_chars(&regions, true)
         };
 
-        let mut line = lines[i].clone();
+        // a line that doesn't fit without wrapping is cut one column earlier
+        // to leave room for the marker at the end of the row
+        let mut rows = wrap::split_rows(&chars, max_line_len, self.wrap);
+        let truncated = self.wrap == WrapMode::Never && rows[0].len() < chars.len();
+        if truncated {
+            rows = wrap::split_rows(&chars, max_line_len - 1, WrapMode::Never);
+        }
+
+        self.print_rows(&rows, truncated, out)?;
+        Ok(())
+    }
+
+    // prints the rows a line was split into, carrying the styles over from
+    // one row to the next. only the first row gets a line number
+    fn print_rows(&self, rows: &[&[StyledChar]], truncated: bool, out: &mut Output)
+        -> io::Result<()> {
+        for (i, row) in rows.iter().enumerate() {
+            let mut line = if i == 0 {
+                self.gutter(&self.line_num.to_string(), self.changes.get(&self.line_num))
+            } else {
+                self.gutter("", None)
+            };
+
+            // print runs of characters with the same style together
+            for run in row.chunk_by(|a, b| a.style == b.style) {
+                let text: String = run.iter().map(|c| c.c).collect();
+                push_part(&mut line, &run[0].style, &text, self.colored);
+            }
 
-        // cut and print the line until it's shorter than max line length
-        while line.len() > max_line_len {
-            println!("\t{}", &line[0..max_line_len]);
-            line = line[max_line_len..].to_string();
+            if truncated {
+                push_part(&mut line, &self.symbol_style, TRUNCATION_MARKER, self.colored);
+            }
+
+            writeln!(out, "{}", line)?;
+        }
+
+        Ok(())
+    }
+
+    fn print_header(&self, name: &str, out: &mut Output) -> io::Result<()> {
+        if self.components.grid {
+            let border = self.border(GRID_TOP);
+            writeln!(out, "{}", border)?;
+        }
+
+        if self.components.header {
+            let mut line = self.gutter("", None);
+            push_part(&mut line, &self.gutter_style, HEADER_LABEL, self.colored);
+            line.push_str(name);
+            writeln!(out, "{}", line)?;
+
+            if self.components.grid {
+                let border = self.border(GRID_MIDDLE);
+                writeln!(out, "{}", border)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn print_footer(&self, out: &mut Output) -> io::Result<()> {
+        if self.components.grid {
+            let border = self.border(GRID_BOTTOM);
+            writeln!(out, "{}", border)?;
+        }
+
+        Ok(())
+    }
+
+    // the columns to the left of the text, num is shown in the line number
+    // column and change in the one before it
+    fn gutter(&self, num: &str, change: Option<&LineChange>) -> String {
+        let mut result = String::new();
+        let mut gutter = String::new();
+
+        // the marker has its own color, the column is there either way
+        if self.components.changes {
+            match change {
+                Some(change) => {
+                    let (marker, color) = match change {
+                        LineChange::Added => (ADDED_MARKER, ADDED_COLOR),
+                        LineChange::Modified => (MODIFIED_MARKER, MODIFIED_COLOR),
+                        LineChange::RemovedAbove => (REMOVED_ABOVE_MARKER, REMOVED_COLOR),
+                        LineChange::RemovedBelow => (REMOVED_BELOW_MARKER, REMOVED_COLOR),
+                    };
+                    let style = Style { foreground: color, ..self.gutter_style };
+                    push_part(&mut result, &style, marker, self.colored);
+                },
+                None => gutter.push(' '),
+            }
+            gutter.push(' ');
         }
-        println!("\t{}", line);
+
+        if self.components.numbers {
+            gutter.push_str(&format!("{:>width$} ", num, width = self.line_num_length));
+        }
+
+        if self.components.grid {
+            gutter.push(GRID_VERTICAL);
+            gutter.push(' ');
+        }
+
+        push_part(&mut result, &self.gutter_style, &gutter, self.colored);
+        result
+    }
+
+    fn gutter_width(&self) -> usize {
+        let mut width = 0;
+
+        if self.components.changes {
+            width += 2;
+        }
+
+        if self.components.numbers {
+            width += self.line_num_length + 1;
+        }
+
+        if self.components.grid {
+            width += 2;
+        }
+
+        width
+    }
+
+    // a horizontal line across the terminal, crossing the grid's vertical line
+    fn border(&self, cross: char) -> String {
+        let mut border = String::new();
+
+        let cross_col = self.gutter_width() - 2;
+        border.extend(std::iter::repeat_n(GRID_HORIZONTAL, cross_col));
+        border.push(cross);
+        border.extend(std::iter::repeat_n(GRID_HORIZONTAL,
+            self.border_width.saturating_sub(cross_col + 1)));
+
+        let mut result = String::new();
+        push_part(&mut result, &self.gutter_style, &border, self.colored);
+        result
+    }
+}
+
+// reads the next line including its line ending, replacing any invalid UTF-8
+fn read_line(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    if reader.read_until(b'\n', &mut buf)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(&buf).into_owned()))
+}
+
+fn count_lines(reader: &mut impl Read) -> io::Result<usize> {
+    let mut buf = [0; COUNT_BUF_SIZE];
+    let mut count = 0;
+    let mut last_byte = b'\n';
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+
+        count += buf[..n].iter().filter(|&&b| b == b'\n').count();
+        last_byte = buf[n - 1];
+    }
+
+    // the last line might not end in a newline
+    if last_byte != b'\n' {
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+fn push_part(row: &mut String, style: &Style, part: &str, colored: bool) {
+    if part.is_empty() {
+        return;
+    }
+
+    if colored {
+        row.push_str(&highlight::style_text(style, part));
+    } else {
+        row.push_str(part);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: Option<isize>, end: Option<isize>) -> LineRange {
+        LineRange { start, end }
+    }
+
+    #[test]
+    fn resolve_open_ends() {
+        assert_eq!(range(None, Some(50)).resolve(200), (1, 50));
+        assert_eq!(range(Some(100), None).resolve(200), (100, 200));
+    }
+
+    #[test]
+    fn resolve_from_end() {
+        assert_eq!(range(Some(-30), None).resolve(200), (171, 200));
+        // ranges reaching back past the first line start at it
+        assert_eq!(range(Some(-30), None).resolve(10), (1, 10));
+    }
+
+    #[test]
+    fn resolve_single_line() {
+        assert_eq!(range(Some(7), Some(7)).resolve(200), (7, 7));
+    }
+
+    #[test]
+    fn counts_from_end() {
+        assert!(range(Some(-30), None).counts_from_end());
+        assert!(range(Some(100), None).counts_from_end());
+        assert!(range(Some(1), Some(-1)).counts_from_end());
+        assert!(!range(None, Some(50)).counts_from_end());
     }
 }
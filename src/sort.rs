@@ -1,8 +1,204 @@
-use std::fs::DirEntry;
+use std::{
+    cmp::Ordering,
+    fs::{ DirEntry, Metadata },
+    io,
+    iter::Peekable,
+    os::unix::fs::{ FileTypeExt, MetadataExt },
+    str::Chars,
+    time::SystemTime,
+};
 
-use crate::Args;
+use crate::{ Args, list };
 
-pub fn sort_files(vec: &mut Vec<DirEntry>, _args: &Args) {
-    vec.sort_by(|a, b|a.file_name().to_ascii_lowercase()
-        .cmp(&b.file_name().to_ascii_lowercase()));
-}
\ No newline at end of file
+/// Sorts directory entries by the field set with `--sort`. Entries that are
+/// equal by that field are sorted by name. Metadata is only fetched once per
+/// entry and only if the field needs it.
+pub fn sort_files(files: &mut Vec<DirEntry>, args: &Args) {
+    if args.sort != "none" {
+        let needs_metadata = !matches!(args.sort.as_str(),
+            "name" | "natural" | "version" | "extension");
+
+        let mut keyed: Vec<(DirEntry, String, Option<Metadata>)> = files.drain(..)
+            .map(|f| {
+                let name = f.file_name().to_string_lossy().to_lowercase();
+                let md = if needs_metadata {
+                    list::get_metadata(f.path(), args).ok()
+                } else {
+                    None
+                };
+                (f, name, md)
+            })
+            .collect();
+
+        keyed.sort_by(|(_, a_name, a_md), (_, b_name, b_md)| {
+            let ordering = match args.sort.as_str() {
+                "natural" | "version" => natural_cmp(a_name, b_name),
+                "extension" => get_extension(a_name).cmp(get_extension(b_name)),
+                "size" => a_md.as_ref().map(|m| m.size()).cmp(&b_md.as_ref().map(|m| m.size())),
+                "modified" => get_time(a_md, Metadata::modified)
+                    .cmp(&get_time(b_md, Metadata::modified)),
+                "changed" => a_md.as_ref().map(|m| (m.ctime(), m.ctime_nsec()))
+                    .cmp(&b_md.as_ref().map(|m| (m.ctime(), m.ctime_nsec()))),
+                "accessed" => get_time(a_md, Metadata::accessed)
+                    .cmp(&get_time(b_md, Metadata::accessed)),
+                "created" => get_time(a_md, Metadata::created)
+                    .cmp(&get_time(b_md, Metadata::created)),
+                "inode" => a_md.as_ref().map(|m| m.ino()).cmp(&b_md.as_ref().map(|m| m.ino())),
+                "type" => get_type_rank(a_md).cmp(&get_type_rank(b_md)),
+                _ => Ordering::Equal,
+            };
+
+            ordering.then_with(|| a_name.cmp(b_name))
+        });
+
+        files.extend(keyed.into_iter().map(|(f, _, _)| f));
+    }
+
+    if args.reverse {
+        files.reverse();
+    }
+
+    // a stable sort keeps the order within the directories and the files
+    if args.group_directories_first {
+        files.sort_by_cached_key(|f| !is_dir(f, args));
+    }
+}
+
+/// Whether an entry counts as a directory for grouping and filtering. Symlinks
+/// to directories only count with `--symlink-dirs` or `--dereference`.
+pub fn is_dir(file: &DirEntry, args: &Args) -> bool {
+    match file.file_type() {
+        Ok(t) if t.is_symlink() => {
+            (args.symlink_dirs || args.dereference) && file.path().is_dir()
+        },
+        Ok(t) => t.is_dir(),
+        Err(_) => false,
+    }
+}
+
+// compares names with runs of digits compared by their value, so that file2
+// comes before file10
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek(), b_chars.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a_c), Some(b_c)) if a_c.is_ascii_digit() && b_c.is_ascii_digit() => {
+                let a_num = take_digits(&mut a_chars);
+                let b_num = take_digits(&mut b_chars);
+
+                // without leading zeros the longer number is the bigger one
+                let a_trimmed = a_num.trim_start_matches('0');
+                let b_trimmed = b_num.trim_start_matches('0');
+                let ordering = a_trimmed.len().cmp(&b_trimmed.len())
+                    .then_with(|| a_trimmed.cmp(b_trimmed))
+                    .then_with(|| a_num.len().cmp(&b_num.len()));
+
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            },
+            (Some(&a_c), Some(&b_c)) => {
+                if a_c != b_c {
+                    return a_c.cmp(&b_c);
+                }
+
+                a_chars.next();
+                b_chars.next();
+            },
+        }
+    }
+}
+
+fn take_digits(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        digits.push(c);
+    }
+    digits
+}
+
+// files without an extension come first, dotfiles don't count as extensions
+fn get_extension(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(i) if i > 0 => &name[i + 1..],
+        _ => "",
+    }
+}
+
+// missing timestamps sort as the oldest
+fn get_time(md: &Option<Metadata>, time: fn(&Metadata) -> io::Result<SystemTime>)
+    -> Option<SystemTime> {
+    md.as_ref().and_then(|m| time(m).ok())
+}
+
+// directories first, then regular files, then everything else
+fn get_type_rank(md: &Option<Metadata>) -> u8 {
+    let file_type = match md {
+        Some(m) => m.file_type(),
+        None => return u8::MAX,
+    };
+
+    if file_type.is_dir() {
+        0
+    } else if file_type.is_file() {
+        1
+    } else if file_type.is_symlink() {
+        2
+    } else if file_type.is_fifo() {
+        3
+    } else if file_type.is_socket() {
+        4
+    } else if file_type.is_block_device() {
+        5
+    } else {
+        6
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_numbers_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file2"), Ordering::Greater);
+        assert_eq!(natural_cmp("file10", "file10"), Ordering::Equal);
+    }
+
+    #[test]
+    fn natural_leading_zeros() {
+        // equal values put the shorter number first
+        assert_eq!(natural_cmp("a1", "a01"), Ordering::Less);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Greater);
+        assert_eq!(natural_cmp("a01", "a2"), Ordering::Less);
+        assert_eq!(natural_cmp("a001b", "a1c"), Ordering::Greater);
+    }
+
+    #[test]
+    fn natural_text_and_prefixes() {
+        assert_eq!(natural_cmp("a", "ab"), Ordering::Less);
+        assert_eq!(natural_cmp("a9", "b1"), Ordering::Less);
+        assert_eq!(natural_cmp("v1.9", "v1.10"), Ordering::Less);
+    }
+
+    #[test]
+    fn extension_of_names() {
+        assert_eq!(get_extension("main.rs"), "rs");
+        assert_eq!(get_extension("archive.tar.gz"), "gz");
+        assert_eq!(get_extension("Makefile"), "");
+        assert_eq!(get_extension(".bashrc"), "");
+    }
+
+    #[test]
+    fn no_extension_sorts_first() {
+        let mut names = vec!["b.rs", ".bashrc", "a.toml", "Makefile", "c.md"];
+        names.sort_by(|a, b| get_extension(a).cmp(get_extension(b)).then_with(|| a.cmp(b)));
+        assert_eq!(names, [".bashrc", "Makefile", "c.md", "b.rs", "a.toml"]);
+    }
+}
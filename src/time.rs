@@ -0,0 +1,175 @@
+use chrono::{ DateTime, Duration, offset::Local, format::{ Item, StrftimeItems } };
+use std::{
+    fs::Metadata,
+    io,
+    os::unix::fs::MetadataExt,
+    time::{ self, SystemTime, UNIX_EPOCH },
+};
+
+// recent times show the time of day, older ones the year like ls
+const DEFAULT_RECENT_FORMAT: &str = "%d %b %H:%M";
+const DEFAULT_OLD_FORMAT: &str = "%d %b  %Y";
+const ISO_RECENT_FORMAT: &str = "%m-%d %H:%M";
+const ISO_OLD_FORMAT: &str = "%Y-%m-%d ";
+const LONG_ISO_FORMAT: &str = "%Y-%m-%d %H:%M";
+const FULL_ISO_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.9f %z";
+// about half of an average year, same as ls
+const RECENT_SECONDS: i64 = 31_556_952 / 2;
+const MISSING_TIME: &str = "-";
+const CUSTOM_PREFIX: char = '+';
+
+// the units of relative times, from the biggest down
+const RELATIVE_UNITS: [(&str, i64); 6] = [
+    ("year", 365 * 24 * 60 * 60),
+    ("month", 30 * 24 * 60 * 60),
+    ("day", 24 * 60 * 60),
+    ("hour", 60 * 60),
+    ("minute", 60),
+    ("second", 1),
+];
+
+/// How timestamps are shown in the long view, set with `--time-style`.
+#[derive(Clone, Debug)]
+pub enum TimeStyle {
+    Default,
+    Iso,
+    LongIso,
+    FullIso,
+    Relative,
+    /// A strftime format given as `+FORMAT`.
+    Custom(String),
+}
+
+impl TimeStyle {
+    /// Parses the name of a preset or a `+FORMAT`, which has to be a valid
+    /// strftime format.
+    pub fn parse(style: &str) -> Option<TimeStyle> {
+        match style {
+            "default" => Some(TimeStyle::Default),
+            "iso" => Some(TimeStyle::Iso),
+            "long-iso" => Some(TimeStyle::LongIso),
+            "full-iso" => Some(TimeStyle::FullIso),
+            "relative" => Some(TimeStyle::Relative),
+            _ => {
+                let format = style.strip_prefix(CUSTOM_PREFIX)?;
+                if StrftimeItems::new(format).any(|i| i == Item::Error) {
+                    return None;
+                }
+
+                Some(TimeStyle::Custom(format.to_string()))
+            },
+        }
+    }
+}
+
+/// Formats a timestamp, which not every file system keeps for every file.
+pub fn format_time(time: io::Result<SystemTime>, style: &TimeStyle) -> String {
+    let dt: DateTime<Local> = match time {
+        Ok(st) => st.into(),
+        Err(_) => return MISSING_TIME.to_string(),
+    };
+
+    // times in the future aren't recent either
+    let age = Local::now().signed_duration_since(dt);
+    let is_recent = age >= Duration::zero() && age.num_seconds() < RECENT_SECONDS;
+
+    let format = match style {
+        TimeStyle::Default if is_recent => DEFAULT_RECENT_FORMAT,
+        TimeStyle::Default => DEFAULT_OLD_FORMAT,
+        TimeStyle::Iso if is_recent => ISO_RECENT_FORMAT,
+        TimeStyle::Iso => ISO_OLD_FORMAT,
+        TimeStyle::LongIso => LONG_ISO_FORMAT,
+        TimeStyle::FullIso => FULL_ISO_FORMAT,
+        TimeStyle::Relative => return format_relative(age),
+        TimeStyle::Custom(format) => format,
+    };
+
+    dt.format(format).to_string()
+}
+
+/// The time the status of a file last changed, which std only has as numbers.
+/// Same shape as the other timestamps of `Metadata`.
+pub fn get_changed(md: &Metadata) -> io::Result<SystemTime> {
+    let secs = time::Duration::from_secs(md.ctime().unsigned_abs());
+    let whole_secs = if md.ctime() < 0 {
+        UNIX_EPOCH - secs
+    } else {
+        UNIX_EPOCH + secs
+    };
+
+    Ok(whole_secs + time::Duration::from_nanos(md.ctime_nsec() as u64))
+}
+
+/// Formats a timestamp for machine-readable output, None if it's missing.
+pub fn format_rfc3339(time: io::Result<SystemTime>) -> Option<String> {
+    let dt: DateTime<Local> = time.ok()?.into();
+    Some(dt.to_rfc3339())
+}
+
+// the age in the biggest unit that fits, like "3 hours ago"
+fn format_relative(age: Duration) -> String {
+    let seconds = age.num_seconds();
+
+    let (unit, size) = RELATIVE_UNITS.iter()
+        .find(|(_, size)| seconds.abs() >= *size)
+        .unwrap_or(&RELATIVE_UNITS[RELATIVE_UNITS.len() - 1]);
+    let count = seconds.abs() / size;
+    let plural = if count == 1 {
+        ""
+    } else {
+        "s"
+    };
+
+    if seconds < 0 {
+        format!("in {} {}{}", count, unit, plural)
+    } else {
+        format!("{} {}{} ago", count, unit, plural)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_presets() {
+        assert!(matches!(TimeStyle::parse("default"), Some(TimeStyle::Default)));
+        assert!(matches!(TimeStyle::parse("iso"), Some(TimeStyle::Iso)));
+        assert!(matches!(TimeStyle::parse("long-iso"), Some(TimeStyle::LongIso)));
+        assert!(matches!(TimeStyle::parse("full-iso"), Some(TimeStyle::FullIso)));
+        assert!(matches!(TimeStyle::parse("relative"), Some(TimeStyle::Relative)));
+    }
+
+    #[test]
+    fn parse_custom_format() {
+        assert!(matches!(TimeStyle::parse("+%Y/%m/%d"),
+            Some(TimeStyle::Custom(f)) if f == "%Y/%m/%d"));
+        assert!(matches!(TimeStyle::parse("+"), Some(TimeStyle::Custom(f)) if f.is_empty()));
+    }
+
+    #[test]
+    fn parse_rejects_unknown_styles() {
+        assert!(TimeStyle::parse("").is_none());
+        assert!(TimeStyle::parse("ISO").is_none());
+        assert!(TimeStyle::parse("%Y-%m-%d").is_none());
+        assert!(TimeStyle::parse("+%Q").is_none());
+    }
+
+    #[test]
+    fn relative_picks_biggest_unit() {
+        assert_eq!(format_relative(Duration::seconds(0)), "0 seconds ago");
+        assert_eq!(format_relative(Duration::seconds(1)), "1 second ago");
+        assert_eq!(format_relative(Duration::seconds(59)), "59 seconds ago");
+        assert_eq!(format_relative(Duration::seconds(60)), "1 minute ago");
+        assert_eq!(format_relative(Duration::seconds(2 * 60 * 60 + 59)), "2 hours ago");
+        assert_eq!(format_relative(Duration::days(1)), "1 day ago");
+        assert_eq!(format_relative(Duration::days(45)), "1 month ago");
+        assert_eq!(format_relative(Duration::days(800)), "2 years ago");
+    }
+
+    #[test]
+    fn relative_future_times() {
+        assert_eq!(format_relative(Duration::seconds(-1)), "in 1 second");
+        assert_eq!(format_relative(Duration::hours(-3)), "in 3 hours");
+    }
+}
@@ -0,0 +1,69 @@
+use std::env;
+
+// the first of these that is set decides the character set, like in the C library
+const LOCALE_VARS: [&str; 3] = ["LC_ALL", "LC_CTYPE", "LANG"];
+
+/// The pieces the lines of a tree are drawn with.
+pub struct TreeParts {
+    branch: &'static str,
+    last_branch: &'static str,
+    line: &'static str,
+    blank: &'static str,
+}
+
+const BOX_PARTS: TreeParts = TreeParts {
+    branch: "├── ",
+    last_branch: "└── ",
+    line: "│   ",
+    blank: "    ",
+};
+
+const ASCII_PARTS: TreeParts = TreeParts {
+    branch: "|-- ",
+    last_branch: "`-- ",
+    line: "|   ",
+    blank: "    ",
+};
+
+impl TreeParts {
+    /// Box-drawing characters unless the locale says the terminal can't show them.
+    pub fn get() -> &'static TreeParts {
+        let locale = LOCALE_VARS.iter()
+            .filter_map(|v| env::var(v).ok())
+            .find(|v| !v.is_empty());
+
+        match locale {
+            Some(l) if !is_utf8(&l) => &ASCII_PARTS,
+            _ => &BOX_PARTS,
+        }
+    }
+
+    /// Builds the prefix of an item from whether it and each of its ancestors
+    /// (not counting the root) are the last item in their directory.
+    pub fn prefix(&self, lasts: &[bool]) -> String {
+        let mut prefix = String::new();
+
+        if let Some((&last, ancestors)) = lasts.split_last() {
+            for &ancestor_last in ancestors {
+                prefix.push_str(if ancestor_last {
+                    self.blank
+                } else {
+                    self.line
+                });
+            }
+
+            prefix.push_str(if last {
+                self.last_branch
+            } else {
+                self.branch
+            });
+        }
+
+        prefix
+    }
+}
+
+fn is_utf8(locale: &str) -> bool {
+    let locale = locale.to_ascii_lowercase();
+    locale.contains("utf-8") || locale.contains("utf8")
+}
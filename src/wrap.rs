@@ -0,0 +1,137 @@
+use syntect::highlighting::Style;
+use unicode_width::UnicodeWidthChar;
+
+use crate::print::TAB_LENGTH;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum WrapMode {
+    Character,
+    Word,
+    Never,
+}
+
+/// A character of a highlighted line along with how many columns it takes up
+/// in the terminal.
+pub struct StyledChar {
+    pub style: Style,
+    pub c: char,
+    pub width: usize,
+}
+
+/// Splits highlighted regions into characters, optionally turning tabs into
+/// spaces up to the next tab stop so that every character has a known width.
+pub fn to_chars<S: AsRef<str>>(regions: &[(Style, S)], expand_tabs: bool) -> Vec<StyledChar> {
+    let mut chars = Vec::new();
+    let mut col = 0;
+
+    for (style, text) in regions {
+        for c in text.as_ref().trim_end_matches('\n').chars() {
+            if c == '\t' && expand_tabs {
+                let width = TAB_LENGTH - col % TAB_LENGTH;
+                for _ in 0..width {
+                    chars.push(StyledChar { style: *style, c: ' ', width: 1 });
+                }
+                col += width;
+                continue;
+            }
+
+            let width = c.width().unwrap_or(0);
+            chars.push(StyledChar { style: *style, c, width });
+            col += width;
+        }
+    }
+
+    chars
+}
+
+/// Splits a line into rows no wider than max_width. In word mode rows are
+/// broken after whitespace when possible and in never mode only the first row
+/// is kept, cut short if it doesn't fit.
+pub fn split_rows(chars: &[StyledChar], max_width: usize, mode: WrapMode) -> Vec<&[StyledChar]> {
+    let mut rows = Vec::new();
+    let mut start = 0;
+    let mut width = 0;
+    // where the row can be broken without splitting a word
+    let mut word_break = None;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let char_width = chars[i].width;
+
+        // a character wider than a whole row still has to go somewhere
+        if width + char_width > max_width && i > start {
+            if mode == WrapMode::Never {
+                return vec![&chars[start..i]];
+            }
+
+            let end = match (mode, word_break) {
+                (WrapMode::Word, Some(b)) if b > start => b,
+                _ => i,
+            };
+            rows.push(&chars[start..end]);
+
+            // the row always ends past its start so the loop makes progress,
+            // characters between the break and i are carried over
+            start = end;
+            width = chars[start..i].iter().map(|c| c.width).sum();
+            word_break = None;
+            continue;
+        }
+
+        width += char_width;
+        if chars[i].c.is_whitespace() {
+            word_break = Some(i + 1);
+        }
+        i += 1;
+    }
+
+    rows.push(&chars[start..]);
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str, max_width: usize, mode: WrapMode) -> Vec<String> {
+        let chars = to_chars(&[(Style::default(), text)], true);
+        split_rows(&chars, max_width, mode).iter()
+            .map(|row| row.iter().map(|c| c.c).collect())
+            .collect()
+    }
+
+    #[test]
+    fn character_mode_splits_anywhere() {
+        assert_eq!(split("abcdef", 4, WrapMode::Character), ["abcd", "ef"]);
+        assert_eq!(split("ab cd ef", 5, WrapMode::Character), ["ab cd", " ef"]);
+    }
+
+    #[test]
+    fn word_mode_breaks_after_whitespace() {
+        assert_eq!(split("ab cd ef", 5, WrapMode::Word), ["ab ", "cd ef"]);
+    }
+
+    #[test]
+    fn word_mode_falls_back_to_characters() {
+        // a word longer than a row is split where the row is full
+        assert_eq!(split("abcdefgh", 3, WrapMode::Word), ["abc", "def", "gh"]);
+        assert_eq!(split("a bcdefgh", 4, WrapMode::Word), ["a ", "bcde", "fgh"]);
+    }
+
+    #[test]
+    fn wide_char_wider_than_row() {
+        assert_eq!(split("中中", 1, WrapMode::Character), ["中", "中"]);
+        assert_eq!(split("a中", 2, WrapMode::Word), ["a", "中"]);
+    }
+
+    #[test]
+    fn never_mode_truncates() {
+        assert_eq!(split("abcdef", 4, WrapMode::Never), ["abcd"]);
+        assert_eq!(split("abc", 4, WrapMode::Never), ["abc"]);
+    }
+
+    #[test]
+    fn empty_line_has_one_row() {
+        assert_eq!(split("", 4, WrapMode::Word), [""]);
+    }
+}